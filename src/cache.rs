@@ -0,0 +1,192 @@
+//! An on-disk cache of GitHub API responses.
+//!
+//! Each response is stored as JSON in the cache directory at the path of its API route (e.g.,
+//! `repos/rust-lang/rfcs/pulls/2000.json`), together with its `ETag` and `Last-Modified` headers.
+//! Cached responses are revalidated with a conditional request; GitHub does not count unchanged
+//! (`304 Not Modified`) responses against the rate limit. In offline mode the network is never
+//! used, so a cache directory can be replayed as a set of recorded fixtures.
+
+use crate::{
+    config::config,
+    errors::{Error, Result},
+    rate_limit,
+};
+use octocrab::{map_github_error, Octocrab};
+use reqwest::{
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    Method, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+/// How the cache is used.
+#[derive(Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheMode {
+    /// Always fetch from GitHub and don't touch the cache.
+    Off,
+    /// Use cached responses if GitHub reports they are unchanged.
+    #[default]
+    Revalidate,
+    /// Only use cached responses, it is an error if a response has not been cached.
+    Offline,
+}
+
+impl FromStr for CacheMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CacheMode> {
+        match s {
+            "off" => Ok(CacheMode::Off),
+            "revalidate" => Ok(CacheMode::Revalidate),
+            "offline" => Ok(CacheMode::Offline),
+            _ => Err(Error::ParseArg(s.to_owned())),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    body: Value,
+}
+
+pub struct Cache {
+    dir: PathBuf,
+    mode: CacheMode,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf, mode: CacheMode) -> Cache {
+        Cache { dir, mode }
+    }
+
+    /// Fetch from GitHub without using the cache.
+    pub fn uncached() -> Cache {
+        Cache::new(PathBuf::new(), CacheMode::Off)
+    }
+
+    /// The cache described by the global config.
+    pub fn from_config() -> Cache {
+        let config = config();
+        Cache::new(config.cache_dir.clone(), config.github_cache)
+    }
+
+    /// Get `route` (e.g., `repos/rust-lang/rfcs/pulls/2000`) from GitHub's API, using the cache.
+    pub async fn get<T: DeserializeOwned>(&self, octocrab: &Octocrab, route: &str) -> Result<T> {
+        let cached = match self.mode {
+            CacheMode::Off => None,
+            _ => self.read(route)?,
+        };
+        if self.mode == CacheMode::Offline {
+            let entry = cached.ok_or_else(|| Error::NotCached(route.to_owned()))?;
+            return Ok(serde_json::from_value(entry.body)?);
+        }
+
+        let mut request = octocrab.request_builder(octocrab.absolute_url(route)?, Method::GET);
+        if let Some(entry) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = rate_limit::send(octocrab, request).await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(entry) = cached {
+                return Ok(serde_json::from_value(entry.body)?);
+            }
+        }
+
+        let response = map_github_error(response).await?;
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|s| s.to_owned())
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        let entry = Entry {
+            etag,
+            last_modified,
+            body: response.json().await?,
+        };
+        if self.mode != CacheMode::Off {
+            self.write(route, &entry)?;
+        }
+        Ok(serde_json::from_value(entry.body)?)
+    }
+
+    fn path(&self, route: &str) -> PathBuf {
+        let mut path = self.dir.join(route.trim_matches('/'));
+        path.set_extension("json");
+        path
+    }
+
+    fn read(&self, route: &str) -> Result<Option<Entry>> {
+        let mut file = match File::open(self.path(route)) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut serialized = String::new();
+        file.read_to_string(&mut serialized)?;
+        Ok(Some(serde_json::from_str(&serialized)?))
+    }
+
+    fn write(&self, route: &str, entry: &Entry) -> Result<()> {
+        let path = self.path(route);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let serialized = serde_json::to_string(entry)?;
+        let mut file = File::create(path)?;
+        file.write_all(serialized.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn offline_() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Cache::new(dir.path().to_owned(), CacheMode::Offline);
+        let entry = Entry {
+            etag: Some("\"abc\"".to_owned()),
+            last_modified: None,
+            body: serde_json::json!({ "number": 2000 }),
+        };
+        cache
+            .write("repos/rust-lang/rfcs/pulls/2000", &entry)
+            .unwrap();
+        assert!(dir
+            .path()
+            .join("repos/rust-lang/rfcs/pulls/2000.json")
+            .exists());
+
+        let octocrab = Octocrab::builder().build().unwrap();
+        let runtime = Runtime::new().unwrap();
+        let body: Value = runtime
+            .block_on(cache.get(&octocrab, "repos/rust-lang/rfcs/pulls/2000"))
+            .unwrap();
+        assert_eq!(body["number"], 2000);
+        assert!(matches!(
+            runtime.block_on(cache.get::<Value>(&octocrab, "repos/rust-lang/rfcs/pulls/1")),
+            Err(Error::NotCached(_))
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+//! Configuration of paths and the upstream RFC repo.
+//!
+//! Configuration is read from `rfc-index.toml` (in the current directory or any of its ancestors,
+//! or a path given with `--config`) and can be overridden on the command line. Relative paths in
+//! the config file are relative to the directory containing the config file. All keys are
+//! optional, e.g.,
+//!
+//! ```toml
+//! metadata_dir = "metadata"
+//! out_dir = "target/out"
+//! static_dir = "static"
+//! template_dir = "templates"
+//! working_dir = "work"
+//! # HTTPS or SSH.
+//! git_url = "https://github.com/rust-lang/rfcs.git"
+//! git_branch = "master"
+//! # Build from a fixed commit (or tag) rather than the tip of `git_branch`.
+//! git_rev = "0123abcd"
+//! # A command which prints a GitHub token, used if `GITHUB_TOKEN` is not set.
+//! github_token_command = "gh auth token"
+//! github_repo = "rust-lang/rfcs"
+//! cache_dir = "target/github-cache"
+//! # `revalidate`, `offline`, or `off`.
+//! github_cache = "revalidate"
+//! # Read PRs and issues from recorded fixtures instead of GitHub (see `forge.rs`).
+//! fixture_dir = "fixtures"
+//! ```
+
+use crate::{cache::CacheMode, diff::DiffFormat, errors::Result};
+use serde::Deserialize;
+use std::{
+    env,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+pub const CONFIG_FILENAME: &str = "rfc-index.toml";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Where RFC and tag metadata is stored.
+    pub metadata_dir: PathBuf,
+    /// Where `generate` writes the website.
+    pub out_dir: PathBuf,
+    /// Static files which are copied to the website.
+    pub static_dir: PathBuf,
+    /// Handlebars templates for the website.
+    pub template_dir: PathBuf,
+    /// Where the RFC repo is cloned.
+    pub working_dir: PathBuf,
+    /// The RFC repo to clone.
+    pub git_url: String,
+    /// The branch of the RFC repo to use.
+    pub git_branch: String,
+    /// A commit (or other revision) of the RFC repo to use instead of the tip of `git_branch`.
+    pub git_rev: Option<String>,
+    /// The RFC repo on GitHub, as `owner/repo`.
+    pub github_repo: String,
+    /// A GitHub personal access token. Prefer `GITHUB_TOKEN` or `github_token_command` so that
+    /// the token is not stored in plain text.
+    pub github_token: Option<String>,
+    /// A shell command which prints a GitHub token, e.g., `gh auth token`.
+    pub github_token_command: Option<String>,
+    /// Where responses from GitHub's API are cached.
+    pub cache_dir: PathBuf,
+    /// How the cache of GitHub responses is used.
+    pub github_cache: CacheMode,
+    /// If set, read PRs and issues from recorded fixtures in this directory instead of GitHub.
+    pub fixture_dir: Option<PathBuf>,
+    /// If set (by `--dry-run`), metadata is not changed, changes are printed in this format
+    /// instead.
+    #[serde(skip)]
+    pub dry_run: Option<DiffFormat>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            metadata_dir: PathBuf::from("metadata"),
+            out_dir: PathBuf::from("target/out"),
+            static_dir: PathBuf::from("static"),
+            template_dir: PathBuf::from("templates"),
+            working_dir: PathBuf::from("work"),
+            git_url: "https://github.com/rust-lang/rfcs.git".to_owned(),
+            git_branch: "master".to_owned(),
+            git_rev: None,
+            github_repo: "rust-lang/rfcs".to_owned(),
+            github_token: None,
+            github_token_command: None,
+            cache_dir: PathBuf::from("target/github-cache"),
+            github_cache: CacheMode::Revalidate,
+            fixture_dir: None,
+            dry_run: None,
+        }
+    }
+}
+
+impl Config {
+    /// Read the config file at `path`, or search for one if `path` is `None`. If there is no
+    /// config file, the default config is used.
+    pub fn load(path: Option<&Path>) -> Result<Config> {
+        let path = match path {
+            Some(p) => p.to_owned(),
+            None => match find_config_file()? {
+                Some(p) => p,
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let mut serialized = String::new();
+        File::open(&path)?.read_to_string(&mut serialized)?;
+        let mut config: Config = toml::from_str(&serialized)?;
+
+        if let Some(base) = path.parent() {
+            for p in [
+                &mut config.metadata_dir,
+                &mut config.out_dir,
+                &mut config.static_dir,
+                &mut config.template_dir,
+                &mut config.working_dir,
+                &mut config.cache_dir,
+            ] {
+                *p = base.join(&*p);
+            }
+            if let Some(dir) = &mut config.fixture_dir {
+                *dir = base.join(&*dir);
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn find_config_file() -> Result<Option<PathBuf>> {
+    let cwd = env::current_dir()?;
+    Ok(cwd
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILENAME))
+        .find(|p| p.exists()))
+}
+
+/// Set the global config, must be called at most once and before calling `config`.
+pub fn init(config: Config) {
+    CONFIG.set(config).expect("config initialised twice");
+}
+
+/// The global config.
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
@@ -0,0 +1,244 @@
+//! Field-level differences between versions of metadata, used to preview changes with
+//! `--dry-run`.
+//!
+//! RFC metadata is compared field by field. The tag metadata is compared tag by tag, and then field
+//! by field.
+
+use crate::errors::{Error, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DiffFormat {
+    /// One line per changed RFC or tag.
+    Plain,
+    /// One JSON object per changed RFC or tag, per line.
+    Json,
+}
+
+impl FromStr for DiffFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<DiffFormat> {
+        match s {
+            "plain" => Ok(DiffFormat::Plain),
+            "json" => Ok(DiffFormat::Json),
+            _ => Err(Error::ParseArg(s.to_owned())),
+        }
+    }
+}
+
+/// A change to one field. `before` is `None` for an added field, `after` for a removed one.
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => write!(f, "~{} ({} -> {})", self.field, before, after),
+            (None, Some(after)) => write!(f, "+{} ({})", self.field, after),
+            _ => write!(f, "-{}", self.field),
+        }
+    }
+}
+
+/// The fields which differ between two versions of an object (ignoring `version`).
+pub fn diff_fields(before: &Value, after: &Value) -> Vec<FieldChange> {
+    let empty = Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+
+    let mut changes = Vec::new();
+    for (k, v) in after {
+        if k == "version" {
+            continue;
+        }
+        match before.get(k) {
+            Some(old) if old == v => {}
+            old => changes.push(FieldChange {
+                field: k.clone(),
+                before: old.cloned(),
+                after: Some(v.clone()),
+            }),
+        }
+    }
+    for (k, v) in before {
+        if !after.contains_key(k) {
+            changes.push(FieldChange {
+                field: k.clone(),
+                before: Some(v.clone()),
+                after: None,
+            });
+        }
+    }
+    changes
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A change to an RFC's metadata, or to a tag in the tag metadata.
+#[derive(Serialize, Debug)]
+pub struct Change {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rfc: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    pub change: ChangeKind,
+    pub fields: Vec<FieldChange>,
+}
+
+impl Change {
+    fn new(before: Option<&Value>, after: Option<&Value>) -> Option<Change> {
+        let null = Value::Null;
+        let change = match (before, after) {
+            (None, Some(_)) => ChangeKind::Added,
+            (Some(_), None) => ChangeKind::Removed,
+            (Some(b), Some(a)) if b != a => ChangeKind::Modified,
+            _ => return None,
+        };
+        let fields = diff_fields(before.unwrap_or(&null), after.unwrap_or(&null));
+        if change == ChangeKind::Modified && fields.is_empty() {
+            return None;
+        }
+        Some(Change {
+            rfc: None,
+            tag: None,
+            change,
+            fields,
+        })
+    }
+
+    pub fn format(&self, format: DiffFormat) -> Result<String> {
+        if format == DiffFormat::Json {
+            return Ok(serde_json::to_string(self)?);
+        }
+
+        let name = match (self.rfc, &self.tag) {
+            (Some(n), _) => format!("{:0>4}", n),
+            (None, Some(t)) => format!("tag {}", t),
+            (None, None) => String::new(),
+        };
+        let fields: Vec<_> = self.fields.iter().map(|c| c.to_string()).collect();
+        Ok(match self.change {
+            ChangeKind::Removed => format!("{}: removed", name),
+            ChangeKind::Added => format!("{} (new): {}", name, fields.join(", ")),
+            ChangeKind::Modified => format!("{}: {}", name, fields.join(", ")),
+        })
+    }
+}
+
+/// The changes between two versions of a metadata file (`None` if the file does not exist).
+pub fn file_changes(before: Option<&str>, after: Option<&str>) -> Result<Vec<Change>> {
+    let before: Option<Value> = before.map(serde_json::from_str).transpose()?;
+    let after: Option<Value> = after.map(serde_json::from_str).transpose()?;
+
+    // The tag metadata is a list of tags.
+    if before
+        .as_ref()
+        .or(after.as_ref())
+        .is_some_and(|v| v.is_array())
+    {
+        let tags = |v: Option<&Value>| -> Vec<(String, Value)> {
+            v.and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .map(|t| (tag_key(t), t.clone()))
+                .collect()
+        };
+        let before = tags(before.as_ref());
+        let after = tags(after.as_ref());
+        let get = |tags: &[(String, Value)], key: &str| {
+            tags.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone())
+        };
+
+        let mut keys: Vec<&String> = before.iter().chain(&after).map(|(k, _)| k).collect();
+        keys.sort();
+        keys.dedup();
+        return Ok(keys
+            .into_iter()
+            .filter_map(|k| {
+                let mut change = Change::new(get(&before, k).as_ref(), get(&after, k).as_ref())?;
+                change.tag = Some(k.clone());
+                Some(change)
+            })
+            .collect());
+    }
+
+    let number = before
+        .as_ref()
+        .or(after.as_ref())
+        .and_then(|v| v.get("number"))
+        .and_then(|n| n.as_u64());
+    Ok(Change::new(before.as_ref(), after.as_ref())
+        .map(|mut change| {
+            change.rfc = number;
+            change
+        })
+        .into_iter()
+        .collect())
+}
+
+/// Identifies a tag in the tag metadata (or a team, in the original format of the tag metadata).
+fn tag_key(tag: &Value) -> String {
+    match tag.get("tag").or_else(|| tag.get("team")) {
+        Some(Value::String(s)) => s.clone(),
+        _ => tag.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn file_changes_() {
+        let format = |before, after, format| -> Vec<String> {
+            file_changes(before, after)
+                .unwrap()
+                .iter()
+                .map(|c| c.format(format).unwrap())
+                .collect()
+        };
+
+        let before = r#"{"version":9,"number":1,"title":null,"tags":["A-traits"]}"#;
+        let after = r#"{"version":9,"number":1,"title":"Private fields","tags":[]}"#;
+        assert_eq!(
+            format(Some(before), Some(after), DiffFormat::Plain),
+            vec![r#"0001: ~tags (["A-traits"] -> []), ~title (null -> "Private fields")"#]
+        );
+        assert_eq!(
+            format(Some(before), Some(after), DiffFormat::Json),
+            vec![
+                r#"{"rfc":1,"change":"modified","fields":[{"field":"tags","before":["A-traits"],"after":[]},{"field":"title","before":null,"after":"Private fields"}]}"#
+            ]
+        );
+        assert_eq!(
+            format(Some(before), None, DiffFormat::Plain),
+            vec!["0001: removed"]
+        );
+        assert!(format(Some(before), Some(before), DiffFormat::Plain).is_empty());
+
+        let before = r#"[{"tag":"A-dyn","teams":["lang"]},{"tag":"A-traits","teams":["lang"]}]"#;
+        let after = r#"[{"tag":"A-traits","aliases":["A-dyn"],"teams":["lang"]}]"#;
+        assert_eq!(
+            format(Some(before), Some(after), DiffFormat::Plain),
+            vec![
+                "tag A-dyn: removed",
+                r#"tag A-traits: +aliases (["A-dyn"])"#
+            ]
+        );
+    }
+}
@@ -22,10 +22,38 @@ pub enum Error {
     HandlebarsRender,
     #[error("Error connecting to or using GitHub's API")]
     GitHub,
+    #[error("Error sending a request to GitHub: {0}")]
+    Http(String),
+    #[error("GitHub API rate limit exceeded, set `GITHUB_TOKEN` to use a higher limit: {0}")]
+    RateLimited(String),
+    #[error("GitHub response not cached (the GitHub cache is in offline mode): {0}")]
+    NotCached(String),
+    #[error("Missing fixture: {0}")]
+    MissingFixture(String),
+    #[error("Error getting a GitHub token: {0}")]
+    GitHubToken(String),
+    #[error("Git error: {0}")]
+    Git(String),
     #[error("Error parsing a user-supplied tag: `{0}`")]
     ParseTag(String),
+    #[error("Tag `{0}` is not in the tag metadata")]
+    UnknownTag(String),
+    #[error("Tag or alias `{0}` already exists")]
+    TagExists(String),
+    #[error("Tag `{0}` is used by {1} RFCs")]
+    TagInUse(String, usize),
+    #[error("Invalid team id: `{0}`")]
+    ParseTeam(String),
+    #[error("Team `{0}` is not in the team metadata")]
+    UnknownTeam(String),
     #[error("Error parsing a command line argument: `{0}`")]
     ParseArg(String),
+    #[error("`{0}` does not support `--dry-run`")]
+    DryRunUnsupported(&'static str),
+    #[error("Error parsing a query: {0}")]
+    ParseQuery(String),
+    #[error("Error in the config file: {0}")]
+    Config(String),
 }
 
 impl From<serde_json::Error> for Error {
@@ -45,6 +73,24 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Error {
+        Error::Http(e.to_string())
+    }
+}
+
+impl From<git2::Error> for Error {
+    fn from(e: git2::Error) -> Error {
+        Error::Git(e.message().to_owned())
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Error {
+        Error::Config(e.to_string())
+    }
+}
+
 impl From<TemplateError> for Error {
     fn from(e: TemplateError) -> Error {
         dbg!(&e);
@@ -61,6 +107,11 @@ impl From<RenderError> for Error {
 
 impl From<octocrab::Error> for Error {
     fn from(e: octocrab::Error) -> Error {
+        if let octocrab::Error::GitHub { source, .. } = &e {
+            if source.message.contains("rate limit") {
+                return Error::RateLimited(source.message.clone());
+            }
+        }
         dbg!(&e);
         Error::GitHub
     }
@@ -0,0 +1,229 @@
+//! Access to the forge which hosts the RFC repo, i.e., its PRs and the RFCs' tracking issues.
+//!
+//! By default this is GitHub (see `github::GitHub`), using the repo named by `github_repo` in the
+//! config. If `fixture_dir` is set, data is read from recorded fixtures instead, which is useful
+//! for testing and working offline. The fixture directory has the following layout:
+//!
+//! ```text
+//! pulls/1234.json                 a `Pr`
+//! pulls/1234.diff                 the diff of an open PR
+//! issues/rust-lang/rust/5678.json an `IssueStatus`
+//! commits/0123abcd....json        a `Pr`, the merged PR which introduced the commit
+//! ```
+
+use crate::{
+    config::config,
+    errors::{Error, Result},
+    github::GitHub,
+    metadata::{IssueRef, IssueStatus},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+pub trait Forge {
+    /// Get the PRs with the given numbers, in the same order.
+    fn get_prs(&self, numbers: &[u64]) -> Result<Vec<Pr>>;
+    /// Get all open PRs and their diffs.
+    fn get_open_prs(&self) -> Result<Vec<(Pr, String)>>;
+    /// Get the state of the given issues. Issues which can't be found are skipped with a warning.
+    fn get_issues(&self, issues: Vec<IssueRef>) -> Result<HashMap<IssueRef, IssueStatus>>;
+    /// Get the merged PR which introduced each of the given commits (by hash). Commits which were
+    /// not merged by a PR are skipped.
+    fn get_commit_prs(&self, commits: Vec<String>) -> Result<HashMap<String, Pr>>;
+}
+
+/// The forge described by the global config.
+pub fn forge() -> Box<dyn Forge> {
+    let config = config();
+    match &config.fixture_dir {
+        Some(dir) => Box::new(Fixtures::new(dir.to_owned())),
+        None => Box::new(GitHub::new(&config.github_repo)),
+    }
+}
+
+/// A PR on the RFC repo.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pr {
+    pub number: u64,
+    pub title: String,
+    /// The username of the PR's author.
+    pub author: String,
+    pub state: PrState,
+    pub labels: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum PrState {
+    Open,
+    Merged,
+    /// Closed without being merged.
+    Closed,
+}
+
+/// A forge backed by recorded data in a directory, see the module docs for the layout.
+pub struct Fixtures {
+    dir: PathBuf,
+}
+
+impl Fixtures {
+    pub fn new(dir: PathBuf) -> Fixtures {
+        Fixtures { dir }
+    }
+
+    fn get_pr(&self, number: u64) -> Result<Pr> {
+        read_fixture(&self.dir.join(format!("pulls/{}.json", number)))
+    }
+}
+
+impl Forge for Fixtures {
+    fn get_prs(&self, numbers: &[u64]) -> Result<Vec<Pr>> {
+        numbers.iter().map(|n| self.get_pr(*n)).collect()
+    }
+
+    fn get_open_prs(&self) -> Result<Vec<(Pr, String)>> {
+        let mut result = Vec::new();
+        for entry in fs::read_dir(self.dir.join("pulls"))? {
+            let path = entry?.path();
+            if path.extension() != Some(OsStr::new("json")) {
+                continue;
+            }
+            let pr: Pr = read_fixture(&path)?;
+            if pr.state != PrState::Open {
+                continue;
+            }
+            let mut diff = String::new();
+            File::open(path.with_extension("diff"))
+                .map_err(|_| {
+                    Error::MissingFixture(path.with_extension("diff").display().to_string())
+                })?
+                .read_to_string(&mut diff)?;
+            result.push((pr, diff));
+        }
+        result.sort_by_key(|(pr, _)| pr.number);
+        Ok(result)
+    }
+
+    fn get_issues(&self, issues: Vec<IssueRef>) -> Result<HashMap<IssueRef, IssueStatus>> {
+        let mut result = HashMap::new();
+        for issue in issues {
+            let path = self
+                .dir
+                .join(format!("issues/{}/{}.json", issue.repo, issue.number));
+            match read_fixture::<IssueStatus>(&path) {
+                Ok(status) => {
+                    result.insert(issue, status);
+                }
+                Err(Error::MissingFixture(_)) => {
+                    eprintln!("warning: could not fetch {}: no fixture", issue)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(result)
+    }
+
+    fn get_commit_prs(&self, commits: Vec<String>) -> Result<HashMap<String, Pr>> {
+        let mut result = HashMap::new();
+        for commit in commits {
+            let path = self.dir.join(format!("commits/{}.json", commit));
+            match read_fixture::<Pr>(&path) {
+                Ok(pr) => {
+                    result.insert(commit, pr);
+                }
+                Err(Error::MissingFixture(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(result)
+    }
+}
+
+fn read_fixture<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let mut serialized = String::new();
+    File::open(path)
+        .map_err(|_| Error::MissingFixture(path.display().to_string()))?
+        .read_to_string(&mut serialized)?;
+    Ok(serde_json::from_str(&serialized)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        github::{get_open_rfc_metadata, get_pr_status},
+        metadata::{RfcMetadata, Status},
+    };
+    use std::io::Write;
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path)
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+    }
+
+    #[test]
+    fn fixtures_() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        write(
+            &dir.join("pulls/1.json"),
+            r#"{"number":1,"title":"Private fields","author":"pcwalton","state":"Merged","labels":["T-lang"]}"#,
+        );
+        write(
+            &dir.join("pulls/2.json"),
+            r#"{"number":2,"title":"Foo","author":"nrc","state":"Open","labels":["final-comment-period"]}"#,
+        );
+        write(
+            &dir.join("pulls/2.diff"),
+            "diff --git a/text/0000-foo.md b/text/0000-foo.md\n--- /dev/null\n+++ b/text/0000-foo.md\n@@ -0,0 +1,2 @@\n+- Start Date: 2022-01-01\n+\n",
+        );
+        write(
+            &dir.join("pulls/3.json"),
+            r#"{"number":3,"title":"Bar","author":"nrc","state":"Closed","labels":["postponed"]}"#,
+        );
+        write(
+            &dir.join("issues/rust-lang/rust/8122.json"),
+            r#"{"issue":"rust-lang/rust#8122","open":false,"labels":[],"updated":"2015-01-01"}"#,
+        );
+        let fixtures = Fixtures::new(dir.to_owned());
+
+        let status = get_pr_status(&fixtures, &[1, 2, 3]).unwrap();
+        assert_eq!(status[&1], Status::Merged);
+        assert_eq!(status[&2], Status::Fcp);
+        assert_eq!(status[&3], Status::Postponed);
+        assert!(fixtures.get_prs(&[4]).is_err());
+
+        let open = get_open_rfc_metadata(&fixtures).unwrap();
+        assert_eq!(open.len(), 1);
+        let metadata: RfcMetadata = open[0].clone().try_into().unwrap();
+        assert_eq!(metadata.number, 2);
+        assert_eq!(metadata.filename, "0000-foo.md");
+        assert_eq!(metadata.status, Status::Fcp);
+        assert_eq!(metadata.title.as_deref(), Some("Foo"));
+
+        let issue: IssueRef = "#8122".parse().unwrap();
+        let issues = fixtures
+            .get_issues(vec![issue.clone(), "#1".parse().unwrap()])
+            .unwrap();
+        assert_eq!(issues.len(), 1);
+        assert!(!issues[&issue].open);
+
+        write(
+            &dir.join("commits/abc.json"),
+            r#"{"number":4,"title":"Amend RFC 1","author":"nrc","state":"Merged","labels":[]}"#,
+        );
+        let prs = fixtures
+            .get_commit_prs(vec!["abc".to_owned(), "def".to_owned()])
+            .unwrap();
+        assert_eq!(prs.len(), 1);
+        assert_eq!(prs["abc"].number, 4);
+    }
+}
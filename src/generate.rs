@@ -1,36 +1,43 @@
 use crate::{
+    config::config,
     errors::Result,
-    github::get_merged_rfc_data,
-    metadata::{open_metadata, read_tag_metadata, Team},
+    github::{get_merged_rfc_data, RfcRepo},
+    metadata::{
+        all_metadata, open_metadata, read_tag_metadata, read_team_metadata, History, Progress,
+        RfcMetadata, Status, TagMetadata,
+    },
+    search::SearchIndex,
 };
+use chrono::{Local, NaiveDate};
 use handlebars::Handlebars;
 use mdbook::utils::render_markdown;
 use serde::Serialize;
 use std::{
+    collections::{HashMap, HashSet},
     fs::{self, File},
     io::Write,
     path::PathBuf,
 };
 
-const OUT_DIR: &str = "target/out";
-const STATIC_DIR: &str = "static";
-const TEMPLATE_DIR: &str = "templates";
-
 const INDEX_TEMPLATE: &str = "index.handlebars";
 const RFC_TEMPLATE: &str = "rfc.handlebars";
+const TAG_TEMPLATE: &str = "tag.handlebars";
+const SEARCH_INDEX: &str = "search-index.js";
+
+pub fn generate(repo: &RfcRepo) -> Result<()> {
+    let config = config();
 
-pub fn generate() -> Result<()> {
     // Make out dir.
     // Ignore errors (might already exist).
-    let _ = fs::create_dir(OUT_DIR);
+    let _ = fs::create_dir_all(&config.out_dir);
 
     // Copy static data. (TODO walk subdirectories)
-    fs::read_dir(STATIC_DIR)?
+    fs::read_dir(&config.static_dir)?
         .filter_map(|e| e.ok())
         .filter(|p| !p.file_type().unwrap().is_dir())
         .map(|entry| {
             let mut to = PathBuf::new();
-            to.push(OUT_DIR);
+            to.push(&config.out_dir);
             to.push(entry.file_name());
             fs::copy(entry.path(), &to)?;
             Ok(())
@@ -41,24 +48,49 @@ pub fn generate() -> Result<()> {
     let mut handlebars = Handlebars::new();
     handlebars.set_strict_mode(true);
     let mut index_path = PathBuf::new();
-    index_path.push(TEMPLATE_DIR);
+    index_path.push(&config.template_dir);
     index_path.push(INDEX_TEMPLATE);
     handlebars.register_template_file("index", index_path)?;
     let mut rfc_path = PathBuf::new();
-    rfc_path.push(TEMPLATE_DIR);
+    rfc_path.push(&config.template_dir);
     rfc_path.push(RFC_TEMPLATE);
     handlebars.register_template_file("rfc", rfc_path)?;
+    handlebars.register_template_file("tag", config.template_dir.join(TAG_TEMPLATE))?;
 
-    let mut rfc_data = get_merged_rfc_data()?;
+    let mut rfc_data = get_merged_rfc_data(repo)?;
+    let revision = repo.revision().map(|hash| RevisionData {
+        short: hash[..7].to_owned(),
+        url: format!("https://github.com/{}/tree/{}", config.github_repo, hash),
+        repo: config.github_repo.clone(),
+        hash,
+    });
     rfc_data.sort();
     let tag_metadata = read_tag_metadata()?;
+    let team_metadata = read_team_metadata()?;
+    let all_rfcs = all_metadata()?;
+    let links: HashMap<u64, RfcLink> = all_rfcs
+        .iter()
+        .map(|m| (m.number, RfcLink::new(m)))
+        .collect();
+    let rfc_links = |numbers: &[u64]| -> Vec<RfcLink> {
+        numbers
+            .iter()
+            .filter_map(|n| links.get(n).cloned())
+            .collect()
+    };
 
+    let today = Local::today().naive_local();
     let mut rfcs = Vec::with_capacity(rfc_data.len());
+    let mut search_index = SearchIndex::new();
 
+    // RFCs with text in the RFC repo, which are not repeated below.
+    let mut with_text = HashSet::new();
     for rfc in rfc_data {
         let metadata = open_metadata(rfc.number)?;
+        with_text.insert(rfc.number);
 
         let number = format!("{:0>4}", rfc.number);
+        let progress = metadata.progress(today).map(ProgressData::from);
         let title = metadata
             .title
             .unwrap_or_else(|| rfc.filename[5..rfc.filename.len() - 3].to_owned());
@@ -67,19 +99,48 @@ pub fn generate() -> Result<()> {
         // RFC pages
         let rfc_text = render_markdown(&rfc.text, false);
         let teams: Vec<_> = metadata.teams.iter().map(|t| t.to_string()).collect();
-        let tags: Vec<_> = metadata.tags.iter().map(trim_prefix).collect();
+        let team_names: Vec<_> = metadata
+            .teams
+            .iter()
+            .map(|t| team_metadata.name(t))
+            .collect();
+        let tags: Vec<_> = metadata.tags.iter().map(|t| tag_id(t)).collect();
+        let status = metadata.status.to_string();
+        search_index.add(&number, &title, &url, &metadata.feature_name, &rfc_text);
         let html = handlebars.render(
             "rfc",
             &RfcTemplateData {
                 number: number.clone(),
                 title: title.clone(),
-                teams: teams.join(" | "),
-                tags: tags.join(" | "),
+                teams: team_names.join(" | "),
+                tags: metadata
+                    .tags
+                    .iter()
+                    .map(|t| TagData::new(t, &tag_metadata))
+                    .collect(),
+                status: status.clone(),
+                progress: progress.clone(),
+                issues: metadata
+                    .issues
+                    .iter()
+                    .map(|i| IssueLink {
+                        name: i.to_string(),
+                        url: i.url(),
+                        state: match metadata.issue_status.iter().find(|s| s.issue == *i) {
+                            Some(s) if s.open => "open".to_owned(),
+                            Some(_) => "closed".to_owned(),
+                            None => String::new(),
+                        },
+                    })
+                    .collect(),
+                history: metadata.history.as_ref().map(HistoryData::from),
+                references: rfc_links(&metadata.references),
+                referenced_by: rfc_links(&metadata.referenced_by),
                 rfc_text,
             },
         )?;
         let mut dest = PathBuf::new();
-        dest.push(OUT_DIR);
+        dest.push(&config.out_dir);
         dest.push(&url);
         let mut file = File::create(dest)?;
         file.write_all(html.as_bytes())?;
@@ -90,70 +151,198 @@ pub fn generate() -> Result<()> {
             url,
             teams,
             tags,
+            status,
+            progress,
+            start_date: start_date(&metadata.start_date),
         };
         rfcs.push(element);
     }
 
-    fn sort(input: &Vec<String>) -> Vec<String> {
-        let mut out: Vec<String> = input.iter().map(trim_prefix).collect();
-        out.sort();
-        out
+    // RFCs which were never merged have no text, so link to their PR instead.
+    for metadata in &all_rfcs {
+        // A proposal's text may have been merged before its status is updated by `scan --merged`.
+        if metadata.status.is_accepted() || with_text.contains(&metadata.number) {
+            continue;
+        }
+
+        let number = format!("{:0>4}", metadata.number);
+        let title = metadata
+            .title
+            .clone()
+            .unwrap_or_else(|| metadata.filename.clone());
+        let url = format!(
+            "https://github.com/{}/pull/{}",
+            config.github_repo, metadata.number
+        );
+        search_index.add(&number, &title, &url, &metadata.feature_name, "");
+        rfcs.push(IndexElement {
+            number,
+            title,
+            url,
+            teams: metadata.teams.iter().map(|t| t.to_string()).collect(),
+            tags: metadata.tags.iter().map(|t| tag_id(t)).collect(),
+            status: metadata.status.to_string(),
+            progress: None,
+            start_date: start_date(&metadata.start_date),
+        });
     }
+    rfcs.sort_by(|a, b| a.number.cmp(&b.number));
 
-    let teams = vec![
-        TeamTemplateData {
-            name: "core".to_owned(),
-            tags: sort(&tag_metadata.by_team[&Team::Core]),
-        },
-        TeamTemplateData {
-            name: "lang".to_owned(),
-            tags: sort(&tag_metadata.by_team[&Team::Lang]),
-        },
-        TeamTemplateData {
-            name: "libs".to_owned(),
-            tags: sort(&tag_metadata.by_team[&Team::Libs]),
-        },
-        TeamTemplateData {
-            name: "compiler".to_owned(),
-            tags: sort(&tag_metadata.by_team[&Team::Compiler]),
-        },
-        TeamTemplateData {
-            name: "tools".to_owned(),
-            tags: sort(&tag_metadata.by_team[&Team::Tools]),
-        },
-        TeamTemplateData {
-            name: "docs".to_owned(),
-            tags: sort(&tag_metadata.by_team[&Team::Docs]),
+    // Deprecated tags are not shown in the index.
+    let sort = |input: Option<&Vec<String>>| -> Vec<TagData> {
+        let mut out: Vec<TagData> = input
+            .into_iter()
+            .flatten()
+            .filter(|t| tag_metadata.get(t).is_none_or(|t| t.deprecated.is_none()))
+            .map(|t| TagData::new(t, &tag_metadata))
+            .collect();
+        out.sort_by(|a, b| a.id.cmp(&b.id));
+        out
+    };
+
+    let teams = team_metadata
+        .teams
+        .iter()
+        .map(|t| TeamTemplateData {
+            name: t.id.to_string(),
+            display_name: t.name().to_owned(),
+            description: t.description.clone(),
+            tags: sort(tag_metadata.by_team.get(&t.id)),
+        })
+        .collect();
+    let statuses = Status::ALL.iter().map(|s| s.to_string()).collect();
+    let html = handlebars.render(
+        "index",
+        &IndexTemplateData {
+            rfcs,
+            teams,
+            statuses,
+            revision,
         },
-    ];
-    let html = handlebars.render("index", &IndexTemplateData { rfcs, teams })?;
+    )?;
     let mut dest = PathBuf::new();
-    dest.push(OUT_DIR);
+    dest.push(&config.out_dir);
     dest.push("index.html");
     let mut file = File::create(dest)?;
     file.write_all(html.as_bytes())?;
 
+    // Tag pages
+    for tag in &tag_metadata.tags {
+        let tags = tag_metadata.descendants(&tag.tag);
+        let rfcs = all_rfcs
+            .iter()
+            .filter(|m| m.tags.iter().any(|t| tags.contains(&&**t)))
+            .map(|m| links[&m.number].clone())
+            .collect();
+        let data = TagData::new(&tag.tag, &tag_metadata);
+        let html = handlebars.render(
+            "tag",
+            &TagTemplateData {
+                parent: tag.parent.as_ref().map(|t| TagData::new(t, &tag_metadata)),
+                children: tag_metadata
+                    .children(&tag.tag)
+                    .iter()
+                    .map(|t| TagData::new(&t.tag, &tag_metadata))
+                    .collect(),
+                deprecated: tag
+                    .deprecated
+                    .as_ref()
+                    .map(|t| TagData::new(t, &tag_metadata)),
+                aliases: tag.aliases.join(", "),
+                teams: tag
+                    .teams
+                    .iter()
+                    .map(|t| t.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+                rfcs,
+                tag: data.clone(),
+            },
+        )?;
+        let mut file = File::create(config.out_dir.join(&data.url))?;
+        file.write_all(html.as_bytes())?;
+    }
+
+    let mut dest = PathBuf::new();
+    dest.push(&config.out_dir);
+    dest.push(SEARCH_INDEX);
+    search_index.write(&dest)?;
+
     Ok(())
 }
 
-fn trim_prefix(t: &String) -> String {
-    if t.starts_with("A-") || t.starts_with("T-") {
-        t[2..].to_lowercase().to_owned()
-    } else {
-        t.to_lowercase().to_owned()
-    }
+fn start_date(date: &Option<NaiveDate>) -> String {
+    date.map(|d| d.to_string()).unwrap_or_default()
 }
 
 #[derive(Serialize, Clone)]
 struct IndexTemplateData {
     rfcs: Vec<IndexElement>,
     teams: Vec<TeamTemplateData>,
+    statuses: Vec<String>,
+    /// The commit of the RFC repo the site was built from.
+    revision: Option<RevisionData>,
+}
+
+#[derive(Serialize, Clone)]
+struct RevisionData {
+    repo: String,
+    hash: String,
+    short: String,
+    url: String,
 }
 
 #[derive(Serialize, Clone)]
 struct TeamTemplateData {
+    /// The team's id, which identifies it in the index page's scripts.
     name: String,
-    tags: Vec<String>,
+    display_name: String,
+    description: Option<String>,
+    tags: Vec<TagData>,
+}
+
+#[derive(Serialize, Clone)]
+struct TagData {
+    /// Identifies the tag in the index page's scripts.
+    id: String,
+    name: String,
+    /// Empty if there is no description.
+    description: String,
+    url: String,
+}
+
+/// Identifies a tag in the index page and in the URL of its page. This keeps the `A-`/`T-` prefix,
+/// e.g., `a-cargo` and `t-cargo`, so that it is unique.
+fn tag_id(tag: &str) -> String {
+    tag.to_lowercase()
+}
+
+impl TagData {
+    fn new(tag: &str, tag_metadata: &TagMetadata) -> TagData {
+        let id = tag_id(tag);
+        TagData {
+            name: tag_metadata.name(tag),
+            description: tag_metadata
+                .get(tag)
+                .and_then(|t| t.description.clone())
+                .unwrap_or_default(),
+            url: format!("tag-{}.html", id),
+            id,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct TagTemplateData {
+    tag: TagData,
+    parent: Option<TagData>,
+    children: Vec<TagData>,
+    /// The tag to use instead, if this tag is deprecated.
+    deprecated: Option<TagData>,
+    aliases: String,
+    teams: String,
+    /// RFCs with this tag or any tag below it.
+    rfcs: Vec<RfcLink>,
 }
 
 #[derive(Serialize, Clone)]
@@ -163,6 +352,10 @@ struct IndexElement {
     url: String,
     teams: Vec<String>,
     tags: Vec<String>,
+    status: String,
+    progress: Option<ProgressData>,
+    /// `YYYY-MM-DD`, or empty if unknown.
+    start_date: String,
 }
 
 #[derive(Serialize, Clone)]
@@ -170,6 +363,121 @@ struct RfcTemplateData {
     number: String,
     title: String,
     teams: String,
-    tags: String,
+    tags: Vec<TagData>,
+    status: String,
+    progress: Option<ProgressData>,
+    issues: Vec<IssueLink>,
+    history: Option<HistoryData>,
+    references: Vec<RfcLink>,
+    referenced_by: Vec<RfcLink>,
     rfc_text: String,
 }
+
+/// A link to another RFC.
+#[derive(Serialize, Clone)]
+struct RfcLink {
+    number: String,
+    title: String,
+    url: String,
+    status: String,
+}
+
+impl RfcLink {
+    fn new(metadata: &RfcMetadata) -> RfcLink {
+        let number = format!("{:0>4}", metadata.number);
+        // RFCs which were never merged have no page, so link to their PR instead.
+        let url = if metadata.status.is_accepted() {
+            format!("{}.html", number)
+        } else {
+            format!(
+                "https://github.com/{}/pull/{}",
+                config().github_repo,
+                metadata.number
+            )
+        };
+        RfcLink {
+            title: metadata
+                .title
+                .clone()
+                .unwrap_or_else(|| metadata.filename.clone()),
+            number,
+            url,
+            status: metadata.status.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct HistoryData {
+    merged: String,
+    modified: String,
+    authors: String,
+    amendments: Vec<AmendmentData>,
+}
+
+impl From<&History> for HistoryData {
+    fn from(h: &History) -> HistoryData {
+        let github_repo = &config().github_repo;
+        HistoryData {
+            merged: h.merged.to_string(),
+            modified: h.modified.to_string(),
+            authors: h.authors.join(", "),
+            amendments: h
+                .amendments
+                .iter()
+                .map(|a| {
+                    let commit_url =
+                        format!("https://github.com/{}/commit/{}", github_repo, a.commit);
+                    let (url, diff_url) = match a.pr {
+                        Some(pr) => {
+                            let url = format!("https://github.com/{}/pull/{}", github_repo, pr);
+                            (url.clone(), format!("{}/files", url))
+                        }
+                        None => (commit_url.clone(), commit_url),
+                    };
+                    AmendmentData {
+                        date: a.date.to_string(),
+                        summary: a.summary.clone(),
+                        author: a.author.clone().unwrap_or_default(),
+                        url,
+                        diff_url,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct AmendmentData {
+    date: String,
+    summary: String,
+    /// Empty if unknown.
+    author: String,
+    /// The PR which made the amendment, or the commit if the PR is unknown.
+    url: String,
+    diff_url: String,
+}
+
+#[derive(Serialize, Clone)]
+struct IssueLink {
+    name: String,
+    url: String,
+    /// `open`, `closed`, or empty if unknown.
+    state: String,
+}
+
+#[derive(Serialize, Clone)]
+struct ProgressData {
+    name: String,
+    id: String,
+}
+
+impl From<Progress> for ProgressData {
+    fn from(p: Progress) -> ProgressData {
+        ProgressData {
+            name: p.to_string(),
+            id: p.id().to_owned(),
+        }
+    }
+}
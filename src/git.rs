@@ -0,0 +1,345 @@
+//! Maintaining the clone of the RFC repo in the working directory, and reading the history of
+//! RFCs from it.
+
+use crate::{
+    config::config,
+    errors::Result,
+    github::github_token,
+    metadata::{leading_number, Amendment, History},
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use git2::{
+    build::{CheckoutBuilder, RepoBuilder},
+    AutotagOption, Commit, Cred, CredentialType, Delta, FetchOptions, Oid, RemoteCallbacks,
+    Repository, Sort,
+};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs,
+    path::Path,
+};
+
+const REMOTE: &str = "origin";
+
+/// Clone the RFC repo into the working directory, or fetch if it has already been cloned, then
+/// check out the configured revision (`git_rev`, or the tip of `git_branch`). Returns the hash of
+/// the commit which was checked out.
+pub fn update_working_repo() -> Result<String> {
+    let config = config();
+    let repo = match Repository::open(&config.working_dir) {
+        Ok(repo) => {
+            repo.remote_set_url(REMOTE, &config.git_url)?;
+            fetch(&repo)?;
+            repo
+        }
+        Err(_) => {
+            eprintln!("Cloning {}", config.git_url);
+            fs::create_dir_all(&config.working_dir)?;
+            RepoBuilder::new()
+                .fetch_options(fetch_options())
+                .branch(&config.git_branch)
+                .clone(&config.git_url, &config.working_dir)?
+        }
+    };
+
+    let commit = match &config.git_rev {
+        Some(rev) => repo.revparse_single(rev)?.peel_to_commit()?,
+        None => repo
+            .find_reference(&format!("refs/remotes/{}/{}", REMOTE, config.git_branch))?
+            .peel_to_commit()?,
+    };
+    // The working repo belongs to us, so discard any local changes.
+    repo.checkout_tree(commit.as_object(), Some(CheckoutBuilder::new().force()))?;
+    repo.set_head_detached(commit.id())?;
+
+    Ok(commit.id().to_string())
+}
+
+fn fetch(repo: &Repository) -> Result<()> {
+    let branch = &config().git_branch;
+    let refspec = format!("+refs/heads/{0}:refs/remotes/{1}/{0}", branch, REMOTE);
+    repo.find_remote(REMOTE)?
+        .fetch(&[refspec], Some(&mut fetch_options()), None)?;
+    Ok(())
+}
+
+/// Fetch options which authenticate (using an SSH agent for SSH URLs, and a GitHub token or git's
+/// credential helpers for HTTPS URLs) and report progress.
+fn fetch_options<'a>() -> FetchOptions<'a> {
+    let mut attempts = 0;
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        // libgit2 keeps asking while authentication fails.
+        attempts += 1;
+        if attempts > 3 {
+            return Err(git2::Error::from_str("authentication failed"));
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username.unwrap_or("git"))
+        } else if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            match github_token() {
+                Ok(Some(token)) => Cred::userpass_plaintext("x-access-token", &token),
+                _ => Cred::credential_helper(&git2::Config::open_default()?, url, username),
+            }
+        } else {
+            Cred::default()
+        }
+    });
+    callbacks.transfer_progress(|progress| {
+        eprint!(
+            "\rReceiving objects: {}/{}",
+            progress.received_objects(),
+            progress.total_objects()
+        );
+        if progress.received_objects() == progress.total_objects() {
+            eprintln!();
+        }
+        true
+    });
+
+    let mut options = FetchOptions::new();
+    options
+        .remote_callbacks(callbacks)
+        .download_tags(AutotagOption::All);
+    options
+}
+
+/// The hash of the commit checked out in the repo at `path`, or `None` if `path` is not in a git
+/// repo.
+pub fn head_revision(path: &Path) -> Option<String> {
+    let repo = Repository::discover(path).ok()?;
+    let id: Oid = repo.head().ok()?.peel_to_commit().ok()?.id();
+    Some(id.to_string())
+}
+
+/// Get the history of each RFC's text from the repo containing `path`, keyed by RFC number.
+///
+/// RFCs are identified by their file name (`NNNN-*.md`) rather than their path, so RFCs which were
+/// moved (e.g., from `active/` to `text/`) keep their history. Dates and amendments come from the
+/// first-parent history of `HEAD`, so each merged PR is a single change. Authors come from all
+/// non-merge commits.
+pub fn rfc_history(path: &Path) -> Result<HashMap<u64, History>> {
+    let repo = Repository::discover(path)?;
+    let mut result = HashMap::new();
+    for commit in walk(&repo, true)? {
+        let date = commit_date(&commit);
+        for number in changed_rfcs(&repo, &commit)? {
+            match result.entry(number) {
+                Entry::Vacant(e) => {
+                    e.insert(History {
+                        merged: date,
+                        modified: date,
+                        authors: Vec::new(),
+                        amendments: Vec::new(),
+                    });
+                }
+                Entry::Occupied(mut e) => {
+                    let history = e.get_mut();
+                    history.modified = date;
+                    history.amendments.push(amendment(&commit, date));
+                }
+            }
+        }
+    }
+
+    for commit in walk(&repo, false)? {
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let author = match commit.author().name() {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        for number in changed_rfcs(&repo, &commit)? {
+            if let Some(history) = result.get_mut(&number) {
+                if !history.authors.contains(&author) {
+                    history.authors.push(author.clone());
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// The commits reachable from `HEAD`, oldest first.
+fn walk(repo: &Repository, first_parent: bool) -> Result<Vec<Commit<'_>>> {
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    walk.push_head()?;
+    if first_parent {
+        walk.simplify_first_parent()?;
+    }
+    walk.map(|id| Ok(repo.find_commit(id?)?)).collect()
+}
+
+/// The RFCs whose text was added or changed by `commit`, compared to its first parent. Text which
+/// was only moved doesn't count.
+fn changed_rfcs(repo: &Repository, commit: &Commit<'_>) -> Result<Vec<u64>> {
+    let parent = match commit.parent_count() {
+        0 => None,
+        _ => Some(commit.parent(0)?.tree()?),
+    };
+    let diff = repo.diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)?;
+    let deleted: Vec<Oid> = diff
+        .deltas()
+        .filter(|d| d.status() == Delta::Deleted)
+        .map(|d| d.old_file().id())
+        .collect();
+
+    let mut result = Vec::new();
+    for delta in diff.deltas() {
+        if delta.status() == Delta::Deleted || deleted.contains(&delta.new_file().id()) {
+            continue;
+        }
+        if let Some(number) = delta.new_file().path().and_then(rfc_number) {
+            if !result.contains(&number) {
+                result.push(number);
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The number of the RFC whose text is at `path`, if any. The template (`0000-template.md`) is
+/// not an RFC.
+fn rfc_number(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    let (number, rest) = name.split_once('-')?;
+    if number.len() != 4 || !rest.ends_with(".md") {
+        return None;
+    }
+    match number.parse() {
+        Ok(0) | Err(_) => None,
+        Ok(n) => Some(n),
+    }
+}
+
+fn commit_date(commit: &Commit<'_>) -> NaiveDate {
+    NaiveDateTime::from_timestamp_opt(commit.time().seconds(), 0)
+        .unwrap_or_else(|| NaiveDateTime::from_timestamp_opt(0, 0).unwrap())
+        .date()
+}
+
+fn amendment(commit: &Commit<'_>, date: NaiveDate) -> Amendment {
+    let message = commit.message().unwrap_or("");
+    let mut lines = message.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.next().unwrap_or("");
+    // GitHub's merge commits are `Merge pull request #1234 from user/branch`, followed by the PR's
+    // title. Squash merges are the PR's title followed by ` (#1234)`.
+    let (pr, summary) = match first.strip_prefix("Merge pull request #") {
+        Some(rest) => (leading_number(rest), lines.next().unwrap_or(first)),
+        None => (
+            first
+                .rsplit_once("(#")
+                .and_then(|(_, number)| leading_number(number)),
+            first,
+        ),
+    };
+    Amendment {
+        commit: commit.id().to_string(),
+        date,
+        pr,
+        summary: summary.to_owned(),
+        author: None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn head_revision_() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        assert_eq!(head_revision(dir.path()), None);
+
+        let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        let id = repo
+            .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap();
+        assert_eq!(head_revision(dir.path()), Some(id.to_string()));
+    }
+
+    #[test]
+    fn rfc_history_() {
+        // Commits the given files, replacing the whole tree.
+        fn commit(repo: &Repository, author: &str, message: &str, files: &[(&str, &str)]) {
+            let root = repo.workdir().unwrap();
+            for entry in fs::read_dir(root).unwrap() {
+                let path = entry.unwrap().path();
+                if path.file_name().unwrap() != ".git" {
+                    fs::remove_dir_all(&path)
+                        .or_else(|_| fs::remove_file(&path))
+                        .unwrap();
+                }
+            }
+            for (path, contents) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+            let mut index = repo.index().unwrap();
+            index
+                .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+                .unwrap();
+            index.update_all(["*"], None).unwrap();
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = git2::Signature::now(author, "test@example.com").unwrap();
+            let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+            let parents: Vec<_> = parent.iter().collect();
+            repo.commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit(
+            &repo,
+            "alice",
+            "Merge pull request #2 from alice/foo\n\nFoo",
+            &[
+                ("0000-template.md", "template"),
+                ("active/0001-foo.md", "foo"),
+            ],
+        );
+        commit(
+            &repo,
+            "bob",
+            "Move RFCs to text",
+            &[
+                ("0000-template.md", "template"),
+                ("text/0001-foo.md", "foo"),
+            ],
+        );
+        commit(
+            &repo,
+            "carol",
+            "Fix a typo (#3)",
+            &[
+                ("0000-template.md", "template"),
+                ("text/0001-foo.md", "Foo"),
+            ],
+        );
+
+        let history = rfc_history(dir.path()).unwrap();
+        assert_eq!(history.len(), 1);
+        let history = &history[&1];
+        assert_eq!(history.authors, vec!["alice", "carol"]);
+        assert_eq!(history.amendments.len(), 1);
+        assert_eq!(history.amendments[0].pr, Some(3));
+        assert_eq!(history.amendments[0].summary, "Fix a typo (#3)");
+    }
+}
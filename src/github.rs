@@ -1,38 +1,82 @@
 use crate::{
+    cache::Cache,
+    config::config,
     errors::{Error, Result},
-    metadata::{RfcMetadata, TagMetadata, Team, TeamTags},
-    parse_multiple,
+    forge::{Forge, Pr, PrState},
+    git::{head_revision, update_working_repo},
+    metadata::{
+        parse_date, History, IssueRef, IssueStatus, RfcMetadata, Status, TagMetadata, Team,
+        TeamMetadata, TeamTags,
+    },
+    parse_multiple, rate_limit,
 };
-use octocrab::{models::pulls::PullRequest, OctocrabBuilder};
+use chrono::NaiveDate;
+use futures::{stream, StreamExt, TryStreamExt};
+use octocrab::{
+    map_github_error,
+    models::{issues::Issue, pulls::PullRequest, IssueState},
+    Octocrab, OctocrabBuilder,
+};
+use reqwest::{header::ACCEPT, Method};
 use std::{
     cmp::Ordering,
     collections::HashMap,
+    env,
     fs::{self, File},
-    io::{BufRead, BufReader, Read},
+    future::Future,
+    io::Read,
     path::PathBuf,
     process::Command,
+    sync::{
+        atomic::{self, AtomicUsize},
+        OnceLock,
+    },
 };
 use tokio::runtime::Runtime;
 
-const GIT_URL: &str = "git@github.com:rust-lang/rfcs.git";
-const GIT_MAIN_BRANCH: &str = "master";
-const WORKING_DIR: &str = "work";
+/// The maximum number of requests to GitHub in flight at once.
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+
 const TEXT_DIR: &str = "text";
 
-/// Initialises a Git repo in the working directory and pull to get it up to date.
-fn init_working_repo() -> Result<()> {
-    // Ignore errors (might already exist).
-    let _ = fs::create_dir(WORKING_DIR);
-    Command::new("git")
-        .current_dir(WORKING_DIR)
-        .args(&["clone", GIT_URL, "."])
-        .output()?;
-    Command::new("git")
-        .current_dir(WORKING_DIR)
-        .args(&["pull", GIT_MAIN_BRANCH, GIT_MAIN_BRANCH])
-        .output()?;
+/// Where to read the RFC repo from.
+#[derive(Debug, Clone)]
+pub enum RfcRepo {
+    /// Clone or update the repo in the working directory.
+    Working,
+    /// Use the repo in the working directory as is, without touching the network.
+    WorkingOffline,
+    /// Use an existing checkout of the repo, without touching the network.
+    Local(PathBuf),
+}
+
+impl RfcRepo {
+    /// Get the root directory of the repo, fetching the repo if necessary.
+    pub fn root(&self) -> Result<PathBuf> {
+        Ok(match self {
+            RfcRepo::Working => {
+                update_working_repo()?;
+                config().working_dir.clone()
+            }
+            RfcRepo::WorkingOffline => config().working_dir.clone(),
+            RfcRepo::Local(path) => path.clone(),
+        })
+    }
+
+    /// Get the directory containing RFC text, fetching the repo if necessary.
+    fn text_dir(&self) -> Result<PathBuf> {
+        let mut result = self.root()?;
+        result.push(TEXT_DIR);
+        Ok(result)
+    }
 
-    Ok(())
+    /// The hash of the commit of the RFC repo which is checked out, if the repo is a git repo.
+    pub fn revision(&self) -> Option<String> {
+        match self {
+            RfcRepo::Working | RfcRepo::WorkingOffline => head_revision(&config().working_dir),
+            RfcRepo::Local(path) => head_revision(path),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +116,10 @@ impl GhMetadata {
     pub fn number(&self) -> Result<u64> {
         rfc_number(&self.filename)
     }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
 }
 
 fn rfc_number(filename: &str) -> Result<u64> {
@@ -82,12 +130,107 @@ impl TryFrom<GhMetadata> for RfcMetadata {
     type Error = Error;
 
     fn try_from(gh: GhMetadata) -> Result<RfcMetadata> {
-        let mut start_date = String::new();
-        let mut feature_name = Vec::new();
-        let mut issues = Vec::new();
-        let file = File::open(&gh.path)?;
-        for line in BufReader::new(file).lines() {
-            let line = line?;
+        let mut text = String::new();
+        File::open(&gh.path)?.read_to_string(&mut text)?;
+        let header = RfcHeader::parse(&text)?;
+        let start_date = header.start_date(&gh.filename);
+
+        let mut rfc = RfcMetadata::new(gh.number()?, gh.filename, start_date);
+        rfc.feature_name = header.feature_name;
+        rfc.issues = header.issues;
+        Ok(rfc)
+    }
+}
+
+/// Metadata about an open RFC PR.
+#[derive(Debug, Clone)]
+pub struct GhOpenMetadata {
+    pub number: u64,
+    title: String,
+    filename: String,
+    /// The text of the proposed RFC, as added by the PR.
+    text: String,
+    labels: Vec<String>,
+}
+
+impl GhOpenMetadata {
+    /// Returns `None` if the PR does not add an RFC text file (e.g., it amends an existing RFC).
+    fn from_pr(pr: &Pr, diff: &str) -> Option<GhOpenMetadata> {
+        let (filename, text) = new_rfc_from_diff(diff)?;
+        Some(GhOpenMetadata {
+            number: pr.number,
+            title: pr.title.clone(),
+            filename,
+            text,
+            labels: pr.labels.clone(),
+        })
+    }
+}
+
+impl TryFrom<GhOpenMetadata> for RfcMetadata {
+    type Error = Error;
+
+    fn try_from(gh: GhOpenMetadata) -> Result<RfcMetadata> {
+        let header = RfcHeader::parse(&gh.text)?;
+        let start_date = header.start_date(&gh.filename);
+
+        let mut rfc = RfcMetadata::new(gh.number, gh.filename, start_date);
+        rfc.status = open_status(&gh.labels);
+        rfc.feature_name = header.feature_name;
+        rfc.issues = header.issues;
+        rfc.title = Some(gh.title);
+        Ok(rfc)
+    }
+}
+
+/// Find the first RFC text file added by a PR diff and return its filename and text.
+fn new_rfc_from_diff(diff: &str) -> Option<(String, String)> {
+    let mut filename = None;
+    let mut text = String::new();
+    let mut is_new = false;
+    for line in diff.lines() {
+        if line.starts_with("diff --git ") {
+            if filename.is_some() {
+                break;
+            }
+            is_new = false;
+        } else if line == "--- /dev/null" {
+            is_new = true;
+        } else if let Some(path) = line.strip_prefix("+++ b/") {
+            let name = path
+                .strip_prefix(TEXT_DIR)
+                .and_then(|p| p.strip_prefix('/'));
+            if let Some(name) = name {
+                if is_new && name.ends_with(".md") {
+                    filename = Some(name.to_owned());
+                }
+            }
+        } else if filename.is_some() {
+            if let Some(line) = line.strip_prefix('+') {
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+    }
+
+    filename.map(|f| (f, text))
+}
+
+/// The metadata found in the header block at the start of an RFC's text.
+struct RfcHeader {
+    start_date: String,
+    feature_name: Vec<String>,
+    issues: Vec<IssueRef>,
+}
+
+impl RfcHeader {
+    fn parse(text: &str) -> Result<RfcHeader> {
+        let mut header = RfcHeader {
+            start_date: String::new(),
+            feature_name: Vec::new(),
+            issues: Vec::new(),
+        };
+        for line in text.lines() {
             let line = line.trim();
             if line.is_empty() {
                 continue;
@@ -98,18 +241,27 @@ impl TryFrom<GhMetadata> for RfcMetadata {
 
             let element: MetaTextElement = line.try_into()?;
             if element.key == "start date" {
-                start_date = element.value;
+                header.start_date = element.value;
             } else if element.key == "feature name" {
-                feature_name = parse_multiple(&element.value);
+                header.feature_name = parse_multiple(&element.value);
             } else if element.key == "rust issue" || element.key == "tracking issues" {
-                issues = parse_multiple(&element.value);
+                header.issues = IssueRef::parse_all(&element.value);
             }
         }
 
-        let mut rfc = RfcMetadata::new(gh.number()?, gh.filename, start_date);
-        rfc.feature_name = feature_name;
-        rfc.issues = issues;
-        Ok(rfc)
+        Ok(header)
+    }
+
+    /// Parse the start date, warning if it can't be parsed.
+    fn start_date(&self, filename: &str) -> Option<NaiveDate> {
+        let result = parse_date(&self.start_date);
+        if result.is_none() {
+            eprintln!(
+                "warning: {}: could not parse start date `{}`",
+                filename, self.start_date
+            );
+        }
+        result
     }
 }
 
@@ -134,11 +286,8 @@ impl<'a> TryFrom<&'a str> for MetaTextElement {
     }
 }
 
-pub fn get_merged_rfc_metadata() -> Result<Vec<GhMetadata>> {
-    init_working_repo()?;
-
-    let mut text_path = PathBuf::from(WORKING_DIR);
-    text_path.push(TEXT_DIR);
+pub fn get_merged_rfc_metadata(repo: &RfcRepo) -> Result<Vec<GhMetadata>> {
+    let text_path = repo.text_dir()?;
 
     let result = fs::read_dir(&text_path)?
         .filter_map(|e| e.ok())
@@ -152,11 +301,8 @@ pub fn get_merged_rfc_metadata() -> Result<Vec<GhMetadata>> {
     Ok(result)
 }
 
-pub fn get_merged_rfc_data() -> Result<Vec<GhData>> {
-    init_working_repo()?;
-
-    let mut text_path = PathBuf::from(WORKING_DIR);
-    text_path.push(TEXT_DIR);
+pub fn get_merged_rfc_data(repo: &RfcRepo) -> Result<Vec<GhData>> {
+    let text_path = repo.text_dir()?;
 
     fs::read_dir(&text_path)?
         .filter_map(|e| e.ok())
@@ -177,25 +323,368 @@ pub fn get_merged_rfc_data() -> Result<Vec<GhData>> {
         .collect()
 }
 
-const LABEL_T_LANG: &str = "T-lang";
-const LABEL_T_CARGO: &str = "T-cargo";
-const LABEL_T_LIBS: &str = "T-libs";
-const LABEL_T_LIBS_API: &str = "T-libs-api";
-const LABEL_T_CORE: &str = "T-core";
-const LABEL_T_COMPILER: &str = "T-compiler";
-const LABEL_T_DEV_TOOLS: &str = "T-dev-tools";
-const LABEL_T_RUSTDOC: &str = "T-rustdoc";
-const LABEL_T_DOC: &str = "T-doc";
+const LABEL_FCP: &str = "final-comment-period";
+const LABEL_PROPOSED_FCP: &str = "proposed-final-comment-period";
+const LABEL_POSTPONED: &str = "postponed";
+
+/// Find a GitHub token from (in order) the `GITHUB_TOKEN` environment variable, the config file, or
+/// the output of the config file's `github_token_command`. Returns `None` if no token is
+/// configured, in which case GitHub's API is used without authentication.
+pub fn github_token() -> Result<Option<String>> {
+    static TOKEN: OnceLock<Option<String>> = OnceLock::new();
+    if let Some(token) = TOKEN.get() {
+        return Ok(token.clone());
+    }
+
+    let config = config();
+    let token = match env::var("GITHUB_TOKEN") {
+        Ok(t) if !t.trim().is_empty() => Some(t.trim().to_owned()),
+        _ => match (&config.github_token, &config.github_token_command) {
+            (Some(t), _) => Some(t.clone()),
+            (None, Some(command)) => Some(run_token_command(command)?),
+            (None, None) => None,
+        },
+    };
+
+    Ok(TOKEN.get_or_init(|| token).clone())
+}
+
+fn run_token_command(command: &str) -> Result<String> {
+    let output = Command::new("sh").args(["-c", command]).output()?;
+    if !output.status.success() {
+        return Err(Error::GitHubToken(format!(
+            "`{}` failed: {}",
+            command,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if token.is_empty() {
+        return Err(Error::GitHubToken(format!(
+            "`{}` printed no token",
+            command
+        )));
+    }
+    Ok(token)
+}
+
+/// The GitHub client, which is shared by all requests.
+fn client() -> Result<&'static Octocrab> {
+    static CLIENT: OnceLock<Octocrab> = OnceLock::new();
+    if let Some(client) = CLIENT.get() {
+        return Ok(client);
+    }
+
+    let mut builder = OctocrabBuilder::new();
+    if let Some(token) = github_token()? {
+        builder = builder.personal_token(token);
+    }
+    let client = builder.build()?;
+    Ok(CLIENT.get_or_init(|| client))
+}
 
-async fn get_pr(number: u64) -> Result<PullRequest> {
-    let pr = OctocrabBuilder::new()
-        .personal_token(include_str!("../token.in").to_owned())
-        .build()
-        .unwrap()
-        .pulls("rust-lang", "rfcs")
-        .get(number)
-        .await?;
-    Ok(pr)
+/// Run a future on the runtime shared by all requests to GitHub.
+fn block_on<F: Future>(future: F) -> F::Output {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME
+        .get_or_init(|| Runtime::new().unwrap())
+        .block_on(future)
+}
+
+/// Run `fetch` for each of `items`, with at most `MAX_CONCURRENT_REQUESTS` running at once, and
+/// report progress. Results are in the same order as `items`. Stops at the first error.
+async fn fetch_all<T, R, F, Fut>(what: &str, items: Vec<T>, fetch: F) -> Result<Vec<R>>
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = Result<R>>,
+{
+    let progress = ProgressReport::new(what, items.len());
+    let result = stream::iter(items)
+        .map(|item| {
+            let future = fetch(item);
+            let progress = &progress;
+            async move {
+                let result = future.await;
+                progress.tick();
+                result
+            }
+        })
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .try_collect()
+        .await;
+    progress.finish();
+    result
+}
+
+/// Reports the progress of a batch of requests on stderr.
+struct ProgressReport {
+    what: String,
+    total: usize,
+    done: AtomicUsize,
+}
+
+impl ProgressReport {
+    fn new(what: &str, total: usize) -> ProgressReport {
+        ProgressReport {
+            what: what.to_owned(),
+            total,
+            done: AtomicUsize::new(0),
+        }
+    }
+
+    fn tick(&self) {
+        let done = self.done.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+        eprint!("\r{}: {}/{}", self.what, done, self.total);
+    }
+
+    fn finish(&self) {
+        if self.total > 0 {
+            eprintln!();
+        }
+    }
+}
+
+/// The forge for a repo on GitHub, accessed using GitHub's API.
+pub struct GitHub {
+    /// `owner/repo`.
+    repo: String,
+}
+
+impl GitHub {
+    pub fn new(repo: &str) -> GitHub {
+        GitHub {
+            repo: repo.to_owned(),
+        }
+    }
+
+    async fn get_pr(&self, number: u64) -> Result<Pr> {
+        let route = format!("repos/{}/pulls/{}", self.repo, number);
+        let pr: PullRequest = Cache::from_config().get(client()?, &route).await?;
+        Ok(pr_from_octocrab(&pr))
+    }
+
+    async fn get_diff(&self, number: u64) -> Result<String> {
+        let octocrab = client()?;
+        let url = octocrab.absolute_url(format!("repos/{}/pulls/{}", self.repo, number))?;
+        let request = octocrab
+            .request_builder(url, Method::GET)
+            .header(ACCEPT, "application/vnd.github.v3.diff");
+        let response = map_github_error(rate_limit::send(octocrab, request).await?).await?;
+        Ok(response.text().await?)
+    }
+
+    async fn get_issue(&self, issue: IssueRef) -> Result<Option<IssueStatus>> {
+        let route = format!("repos/{}/issues/{}", issue.repo, issue.number);
+        match Cache::from_config().get::<Issue>(client()?, &route).await {
+            Ok(i) => Ok(Some(IssueStatus {
+                issue,
+                open: i.state == "open",
+                labels: i.labels.into_iter().map(|l| l.name).collect(),
+                updated: i.updated_at.naive_utc().date(),
+            })),
+            Err(e @ (Error::RateLimited(_) | Error::NotCached(_))) => Err(e),
+            Err(e) => {
+                eprintln!("\nwarning: could not fetch {}: {:?}", issue, e);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn get_commit_pr(&self, commit: String) -> Result<Option<(String, Pr)>> {
+        let route = format!("repos/{}/commits/{}/pulls", self.repo, commit);
+        let prs: Vec<PullRequest> = Cache::from_config().get(client()?, &route).await?;
+        Ok(prs
+            .iter()
+            .find(|pr| pr.merged_at.is_some())
+            .map(|pr| (commit, pr_from_octocrab(pr))))
+    }
+}
+
+fn pr_from_octocrab(pr: &PullRequest) -> Pr {
+    let state = if pr.merged_at.is_some() {
+        PrState::Merged
+    } else if pr.state == IssueState::Open {
+        PrState::Open
+    } else {
+        PrState::Closed
+    };
+    Pr {
+        number: pr.number,
+        title: pr.title.clone(),
+        author: pr.user.login.clone(),
+        state,
+        labels: pr.labels.iter().flatten().map(|l| l.name.clone()).collect(),
+    }
+}
+
+impl Forge for GitHub {
+    fn get_prs(&self, numbers: &[u64]) -> Result<Vec<Pr>> {
+        block_on(fetch_all("Fetching PRs", numbers.to_vec(), |n| {
+            self.get_pr(n)
+        }))
+    }
+
+    fn get_open_prs(&self) -> Result<Vec<(Pr, String)>> {
+        const PER_PAGE: usize = 100;
+
+        block_on(async {
+            // The list of open PRs changes too often to be worth caching.
+            let uncached = Cache::uncached();
+            let mut prs = Vec::new();
+            for page in 1.. {
+                let route = format!(
+                    "repos/{}/pulls?state=open&per_page={}&page={}",
+                    self.repo, PER_PAGE, page
+                );
+                let items: Vec<PullRequest> = uncached.get(client()?, &route).await?;
+                let last = items.len() < PER_PAGE;
+                prs.extend(items.iter().map(pr_from_octocrab));
+                if last {
+                    break;
+                }
+            }
+
+            let numbers = prs.iter().map(|pr| pr.number).collect();
+            let diffs = fetch_all("Fetching diffs", numbers, |n| self.get_diff(n)).await?;
+            Ok(prs.into_iter().zip(diffs).collect())
+        })
+    }
+
+    fn get_issues(&self, issues: Vec<IssueRef>) -> Result<HashMap<IssueRef, IssueStatus>> {
+        let statuses = block_on(fetch_all("Fetching tracking issues", issues, |i| {
+            self.get_issue(i)
+        }))?;
+        Ok(statuses
+            .into_iter()
+            .flatten()
+            .map(|s| (s.issue.clone(), s))
+            .collect())
+    }
+
+    fn get_commit_prs(&self, commits: Vec<String>) -> Result<HashMap<String, Pr>> {
+        let prs = block_on(fetch_all("Fetching amendment PRs", commits, |c| {
+            self.get_commit_pr(c)
+        }))?;
+        Ok(prs.into_iter().flatten().collect())
+    }
+}
+
+/// Fetch the labels of the PRs of the given RFCs.
+pub fn get_pr_labels(forge: &dyn Forge, numbers: &[u64]) -> Result<HashMap<u64, Vec<String>>> {
+    Ok(forge
+        .get_prs(numbers)?
+        .into_iter()
+        .map(|pr| (pr.number, pr.labels))
+        .collect())
+}
+
+/// The status of an open PR, based on its labels.
+fn open_status(labels: &[String]) -> Status {
+    if labels
+        .iter()
+        .any(|l| l == LABEL_FCP || l == LABEL_PROPOSED_FCP)
+    {
+        Status::Fcp
+    } else {
+        Status::Proposed
+    }
+}
+
+/// The status of an RFC, based on its PR.
+fn pr_status(pr: &Pr) -> Status {
+    match pr.state {
+        PrState::Merged => Status::Merged,
+        PrState::Open => open_status(&pr.labels),
+        PrState::Closed if pr.labels.iter().any(|l| l == LABEL_POSTPONED) => Status::Postponed,
+        PrState::Closed => Status::Closed,
+    }
+}
+
+/// Fetch the current status of the PRs of the given RFCs.
+pub fn get_pr_status(forge: &dyn Forge, numbers: &[u64]) -> Result<HashMap<u64, Status>> {
+    Ok(forge
+        .get_prs(numbers)?
+        .iter()
+        .map(|pr| (pr.number, pr_status(pr)))
+        .collect())
+}
+
+/// Fetch all open PRs to the RFC repo which propose a new RFC.
+pub fn get_open_rfc_metadata(forge: &dyn Forge) -> Result<Vec<GhOpenMetadata>> {
+    let mut result = Vec::new();
+    for (pr, diff) in forge.get_open_prs()? {
+        match GhOpenMetadata::from_pr(&pr, &diff) {
+            Some(m) => result.push(m),
+            None => eprintln!("warning: PR {} does not add an RFC", pr.number),
+        }
+    }
+    Ok(result)
+}
+
+/// Update the history of accepted RFCs from the history of the RFC repo (see `rfc_history`).
+/// Unless `force`, what was already fetched about the PRs of amendments is kept; the PRs of any
+/// other amendments are fetched. Returns the numbers of the changed RFCs. Does not save the
+/// updated metadata to disk.
+pub fn update_history(
+    forge: &dyn Forge,
+    metadata: &mut [RfcMetadata],
+    mut history: HashMap<u64, History>,
+    force: bool,
+) -> Result<Vec<u64>> {
+    let mut changed = Vec::new();
+    for m in metadata.iter_mut() {
+        if !m.status.is_accepted() {
+            continue;
+        }
+        let mut history = history.remove(&m.number);
+        match (&mut history, &m.history) {
+            (None, _) => eprintln!(
+                "warning: RFC {}: no history found in the RFC repo",
+                m.number
+            ),
+            (Some(new), Some(old)) if !force => {
+                for amendment in &mut new.amendments {
+                    if let Some(a) = old.amendments.iter().find(|a| a.commit == amendment.commit) {
+                        *amendment = a.clone();
+                    }
+                }
+            }
+            _ => {}
+        }
+        if force || m.history != history {
+            m.history = history;
+            changed.push(m.number);
+        }
+    }
+
+    // Including amendments whose PRs were not found before.
+    let commits: Vec<String> = metadata
+        .iter()
+        .filter(|m| m.status.is_accepted())
+        .flat_map(|m| &m.history)
+        .flat_map(|h| &h.amendments)
+        .filter(|a| a.author.is_none())
+        .map(|a| a.commit.clone())
+        .collect();
+    if commits.is_empty() {
+        return Ok(changed);
+    }
+    let prs = forge.get_commit_prs(commits)?;
+
+    for m in metadata.iter_mut() {
+        for amendment in m.history.iter_mut().flat_map(|h| &mut h.amendments) {
+            if let Some(pr) = prs.get(&amendment.commit) {
+                amendment.pr = Some(pr.number);
+                amendment.summary = pr.title.clone();
+                amendment.author = Some(pr.author.clone());
+                if !changed.contains(&m.number) {
+                    changed.push(m.number);
+                }
+            }
+        }
+    }
+    Ok(changed)
 }
 
 pub struct UpdateOptions {
@@ -212,64 +701,65 @@ impl UpdateOptions {
     }
 }
 
+/// Update an RFC's metadata from its PR's labels (see `get_pr_labels`). Does not save the updated
+/// metadata to disk.
 pub fn update_from_pr(
     metadata: &mut RfcMetadata,
+    labels: &[String],
     tag_metadata: &TagMetadata,
+    team_metadata: &TeamMetadata,
     opts: UpdateOptions,
-) -> Result<()> {
-    if !opts.tags && !opts.teams {
-        return Ok(());
-    }
-
-    Runtime::new().unwrap().block_on(async {
-        let pr = get_pr(metadata.number).await?;
-
-        match &pr.labels {
-            Some(l) => {
-                if opts.teams {
-                    // Teams
-                    let teams = l.iter().filter_map(|l| match &*l.name {
-                        LABEL_T_LANG => Some(Team::Lang),
-                        LABEL_T_LIBS | LABEL_T_LIBS_API => Some(Team::Libs),
-                        LABEL_T_CORE => Some(Team::Core),
-                        LABEL_T_COMPILER => Some(Team::Compiler),
-                        LABEL_T_DEV_TOOLS | LABEL_T_RUSTDOC | LABEL_T_CARGO => Some(Team::Tools),
-                        LABEL_T_DOC => Some(Team::Docs),
-                        _ => None,
-                    });
-
-                    for team in teams {
-                        if !metadata.teams.contains(&team) {
-                            metadata.teams.push(team);
-                        }
-                    }
-                }
+) {
+    update_from_labels(metadata, labels, tag_metadata, team_metadata, opts);
+}
 
-                if opts.tags {
-                    // Tags
-                    for l in l {
-                        let l = &l.name;
-                        if tag_metadata.by_tag.contains_key(l) {
-                            let l = l.to_owned();
-                            if !metadata.tags.contains(&l) {
-                                metadata.tags.push(l);
-                            }
-                        }
-                    }
-                }
+/// Update an open RFC's metadata from the PR's labels. Does not save the updated metadata to disk.
+pub fn update_from_open_pr(
+    metadata: &mut RfcMetadata,
+    pr: &GhOpenMetadata,
+    tag_metadata: &TagMetadata,
+    team_metadata: &TeamMetadata,
+    opts: UpdateOptions,
+) {
+    update_from_labels(metadata, &pr.labels, tag_metadata, team_metadata, opts);
+}
+
+fn update_from_labels(
+    metadata: &mut RfcMetadata,
+    labels: &[String],
+    tag_metadata: &TagMetadata,
+    team_metadata: &TeamMetadata,
+    opts: UpdateOptions,
+) {
+    if opts.teams {
+        // Teams
+        let teams = labels.iter().filter_map(|l| team_metadata.for_label(l));
+
+        for team in teams {
+            if !metadata.teams.contains(team) {
+                metadata.teams.push(team.clone());
             }
-            None => return Err(Error::GitHub), // format!("No labels for PR {}", metadata.number),
         }
+    }
 
-        Ok(())
-    })
+    if opts.tags {
+        // Tags
+        for l in labels {
+            if let Some(tag) = tag_metadata.resolve(l) {
+                if !metadata.tags.iter().any(|t| t == tag) {
+                    metadata.tags.push(tag.to_owned());
+                }
+            }
+        }
+    }
 }
 
-pub fn init_tag_metadata() -> Result<Vec<TeamTags>> {
-    init_working_repo()?;
-
-    let mut text_path = PathBuf::from(WORKING_DIR);
-    text_path.push(TEXT_DIR);
+pub fn init_tag_metadata(
+    repo: &RfcRepo,
+    forge: &dyn Forge,
+    team_metadata: &TeamMetadata,
+) -> Result<Vec<TeamTags>> {
+    let text_path = repo.text_dir()?;
 
     let numbers = fs::read_dir(&text_path)?
         .filter_map(|e| e.ok())
@@ -281,14 +771,9 @@ pub fn init_tag_metadata() -> Result<Vec<TeamTags>> {
         })
         .collect::<Result<Vec<u64>>>()?;
 
-    Runtime::new().unwrap().block_on(async {
-        let mut result = HashMap::new();
-        result.insert(Team::Lang, Vec::new());
-        result.insert(Team::Libs, Vec::new());
-        result.insert(Team::Core, Vec::new());
-        result.insert(Team::Tools, Vec::new());
-        result.insert(Team::Compiler, Vec::new());
-        result.insert(Team::Docs, Vec::new());
+    let labels = get_pr_labels(forge, &numbers)?;
+    {
+        let mut result: HashMap<_, Vec<String>> = HashMap::new();
 
         enum TeamOrTag {
             Team(Team),
@@ -296,22 +781,14 @@ pub fn init_tag_metadata() -> Result<Vec<TeamTags>> {
         }
 
         for n in numbers {
-            let pr = get_pr(n).await?;
-            match &pr.labels {
+            match labels.get(&n) {
                 Some(l) => {
                     let (teams, tags): (Vec<TeamOrTag>, _) = l
                         .iter()
-                        .filter(|l| l.name.starts_with("T-") || l.name.starts_with("A-"))
-                        .map(|l| match &*l.name {
-                            LABEL_T_LANG => TeamOrTag::Team(Team::Lang),
-                            LABEL_T_LIBS_API => TeamOrTag::Team(Team::Libs),
-                            LABEL_T_CORE => TeamOrTag::Team(Team::Core),
-                            LABEL_T_COMPILER => TeamOrTag::Team(Team::Compiler),
-                            LABEL_T_DEV_TOOLS | LABEL_T_RUSTDOC | LABEL_T_CARGO => {
-                                TeamOrTag::Team(Team::Tools)
-                            }
-                            LABEL_T_DOC => TeamOrTag::Team(Team::Docs),
-                            s => TeamOrTag::Tag(s.to_owned()),
+                        .filter(|l| l.starts_with("T-") || l.starts_with("A-"))
+                        .map(|l| match team_metadata.for_label(l) {
+                            Some(team) => TeamOrTag::Team(team.clone()),
+                            None => TeamOrTag::Tag(l.to_owned()),
                         })
                         .partition(|tt| matches!(tt, TeamOrTag::Team(_)));
 
@@ -338,7 +815,7 @@ pub fn init_tag_metadata() -> Result<Vec<TeamTags>> {
                         0 => println!("No teams: [{}]", tags.join(", ")),
                         1 => {
                             for t in tags {
-                                let v = &mut result.get_mut(&teams[0]).unwrap();
+                                let v = result.entry(teams[0].clone()).or_default();
                                 if !v.contains(&t) {
                                     v.push(t);
                                 }
@@ -355,5 +832,119 @@ pub fn init_tag_metadata() -> Result<Vec<TeamTags>> {
             .into_iter()
             .map(|(team, tags)| TeamTags { team, tags })
             .collect())
-    })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{forge::Fixtures, metadata::Amendment};
+
+    #[test]
+    fn new_rfc_from_diff_() {
+        let diff = "diff --git a/README.md b/README.md
+--- a/README.md
++++ b/README.md
+@@ -1,1 +1,1 @@
+-foo
++bar
+diff --git a/text/0000-foo.md b/text/0000-foo.md
+new file mode 100644
+--- /dev/null
++++ b/text/0000-foo.md
+@@ -0,0 +1,3 @@
++- Feature Name: `foo`
++- Start Date: 2022-01-01
++
+";
+        let (filename, text) = new_rfc_from_diff(diff).unwrap();
+        assert_eq!(filename, "0000-foo.md");
+        assert_eq!(text, "- Feature Name: `foo`\n- Start Date: 2022-01-01\n\n");
+
+        let header = RfcHeader::parse(&text).unwrap();
+        assert_eq!(header.start_date, "2022-01-01");
+        assert_eq!(header.feature_name, vec!["foo".to_owned()]);
+
+        let amendment = "diff --git a/text/0001-foo.md b/text/0001-foo.md
+--- a/text/0001-foo.md
++++ b/text/0001-foo.md
+@@ -1,1 +1,1 @@
+-foo
++bar
+";
+        assert!(new_rfc_from_diff(amendment).is_none());
+    }
+
+    #[test]
+    fn update_history_() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("commits")).unwrap();
+        for (commit, number) in [("a1", 10), ("b1", 11), ("c1", 12)] {
+            let pr = format!(
+                r#"{{"number":{},"title":"Fetched","author":"nrc","state":"Merged","labels":[]}}"#,
+                number
+            );
+            fs::write(dir.join(format!("commits/{}.json", commit)), pr).unwrap();
+        }
+        let fixtures = Fixtures::new(dir.to_owned());
+
+        let date = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let amendment = |commit: &str, author: Option<&str>| Amendment {
+            commit: commit.to_owned(),
+            date,
+            pr: author.map(|_| 1),
+            summary: "Stored".to_owned(),
+            author: author.map(|a| a.to_owned()),
+        };
+        let history = |amendments| History {
+            merged: date,
+            modified: date,
+            authors: Vec::new(),
+            amendments,
+        };
+        let mut metadata: Vec<_> = (1..=4)
+            .map(|n| {
+                let mut m = RfcMetadata::new(n, String::new(), None);
+                m.status = Status::Merged;
+                m
+            })
+            .collect();
+        // RFC 1 has a new amendment, RFC 2's amendment has not been fetched (e.g., it was
+        // migrated), and RFC 3 is up to date.
+        metadata[0].history = Some(history(vec![amendment("a0", Some("old"))]));
+        metadata[1].history = Some(history(vec![amendment("b1", None)]));
+        metadata[2].history = Some(history(vec![amendment("c1", Some("old"))]));
+        metadata[3].status = Status::Closed;
+
+        let mut repo_history = HashMap::new();
+        repo_history.insert(
+            1,
+            history(vec![amendment("a0", None), amendment("a1", None)]),
+        );
+        repo_history.insert(2, history(vec![amendment("b1", None)]));
+        repo_history.insert(3, history(vec![amendment("c1", None)]));
+
+        let changed =
+            update_history(&fixtures, &mut metadata, repo_history.clone(), false).unwrap();
+        assert_eq!(changed, vec![1, 2]);
+        let amendments = &metadata[0].history.as_ref().unwrap().amendments;
+        assert_eq!(amendments[0], amendment("a0", Some("old")));
+        assert_eq!(amendments[1].pr, Some(10));
+        assert_eq!(amendments[1].author.as_deref(), Some("nrc"));
+        assert_eq!(amendments[1].summary, "Fetched");
+        let amendments = &metadata[1].history.as_ref().unwrap().amendments;
+        assert_eq!(amendments[0].pr, Some(11));
+        assert_eq!(
+            metadata[2].history,
+            Some(history(vec![amendment("c1", Some("old"))]))
+        );
+        assert_eq!(metadata[3].history, None);
+
+        // Everything is fetched again with `force`.
+        let changed = update_history(&fixtures, &mut metadata, repo_history, true).unwrap();
+        assert_eq!(changed, vec![1, 2, 3]);
+        let amendments = &metadata[2].history.as_ref().unwrap().amendments;
+        assert_eq!(amendments[0].pr, Some(12));
+    }
 }
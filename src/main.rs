@@ -1,23 +1,64 @@
-#![feature(iter_intersperse)]
-
 use crate::{
+    cache::CacheMode,
+    config::{config, Config},
+    diff::DiffFormat,
     errors::{Error, Result},
-    github::{get_merged_rfc_metadata, init_tag_metadata, update_from_pr, UpdateOptions},
+    forge::forge,
+    git::rfc_history,
+    github::{
+        get_merged_rfc_data, get_merged_rfc_metadata, get_open_rfc_metadata, get_pr_labels,
+        get_pr_status, init_tag_metadata, update_from_open_pr, update_from_pr, update_history,
+        RfcRepo, UpdateOptions,
+    },
     metadata::{
-        all_metadata, all_metadata_numbers, delete_metadata, infer_team_from_tags, metadata_exists,
-        open_metadata, read_tag_metadata, save_metadata, write_tag_metadata, RfcMetadata, Team,
+        add_team_tags, all_metadata, all_metadata_numbers, delete_metadata, infer_team_from_tags,
+        metadata_exists, open_metadata, read_tag_metadata, read_tags, read_team_metadata, recover,
+        save_metadata, tag_metadata_exists, write_tag_metadata, IssueRef, Progress, RfcMetadata,
+        Status, Tag, Transaction,
     },
+    references::update_references,
 };
-use std::{process, str::FromStr};
+use chrono::{Datelike, Local, NaiveDate};
+use output::Format;
+use query::Query;
+use std::{collections::BTreeMap, path::PathBuf, process, str::FromStr};
 use structopt::StructOpt;
 
+mod cache;
+mod config;
+mod diff;
 mod errors;
+mod forge;
 mod generate;
+mod git;
 mod github;
 mod metadata;
+mod migrate;
+mod output;
+mod query;
+mod rate_limit;
+mod references;
+mod search;
+mod taxonomy;
+mod validate;
 
 fn main() {
-    match Command::from_args() {
+    let opts = Opts::from_args();
+    match opts.config() {
+        Ok(c) => config::init(c),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            process::exit(ExitCode::Other as i32);
+        }
+    }
+    if config().dry_run.is_none() {
+        if let Err(e) = recover(&config().metadata_dir) {
+            eprintln!("Error: {:?}", e);
+            process::exit(ExitCode::Other as i32);
+        }
+    }
+
+    match opts.command {
         Command::Add {
             number,
             force,
@@ -27,47 +68,179 @@ fn main() {
         Command::Get {
             number,
             verbose,
+            format,
             flags,
-        } => run_get(number, verbose, flags),
+        } => run_get(number, verbose, format, flags),
         Command::Delete { number } => run_delete(number),
         Command::Scan {
             open,
             merged,
+            issues,
+            history,
+            references,
             force,
+            repo,
         } => {
+            let repo = repo.into();
             if open {
                 run_scan_open(force);
             }
             if merged {
-                run_scan_merged(force);
+                run_scan_merged(&repo, force);
             }
-            if !open && !merged {
-                eprintln!("warning: no scan run, use either --merged or --open");
+            if issues {
+                run_scan_issues(force);
+            }
+            if history {
+                run_scan_history(&repo, force);
+            }
+            if references {
+                run_scan_references(&repo);
+            }
+            if !open && !merged && !issues && !history && !references {
+                eprintln!(
+                    "warning: no scan run, use --merged, --open, --issues, --history, or --references"
+                );
             }
         }
         Command::Stats => run_stats(),
-        Command::Generate => run_generate(),
-        Command::Query { tag } => run_query(tag),
+        Command::Generate { repo } => run_generate(repo.into()),
+        Command::Validate { repo } => run_validate(repo.into()),
+        Command::Query {
+            query,
+            tag,
+            status,
+            format,
+            fields,
+        } => run_query(query, tag, status, format, fields),
         Command::Tag {
             numbers,
+            query,
+            add,
+            scan,
+            init,
+            remove,
+            replace,
+            infer_team,
+            repo,
+        } => run_tag(
+            numbers,
+            query,
             add,
             scan,
             init,
             remove,
             replace,
             infer_team,
-        } => run_tag(numbers, add, scan, init, remove, replace, infer_team),
+            repo.into(),
+        ),
         Command::Team {
             numbers,
+            query,
             add,
             remove,
             replace,
-        } => run_team(numbers, add, remove, replace),
+        } => run_team(numbers, query, add, remove, replace),
+        Command::Tags { command } => run_tags(command),
         Command::Migrate => run_migrate(),
     }
 }
 
 /// A utility for building the RFC index website and maintaining its metadata.
+#[derive(StructOpt)]
+struct Opts {
+    /// Config file, defaults to `rfc-index.toml` in the current directory or its ancestors.
+    #[structopt(long, global = true, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Directory of RFC metadata (overrides the config file).
+    #[structopt(long, global = true, parse(from_os_str))]
+    metadata_dir: Option<PathBuf>,
+    /// Directory for the generated website (overrides the config file).
+    #[structopt(long, global = true, parse(from_os_str))]
+    out_dir: Option<PathBuf>,
+    /// Directory of static files for the website (overrides the config file).
+    #[structopt(long, global = true, parse(from_os_str))]
+    static_dir: Option<PathBuf>,
+    /// Directory of website templates (overrides the config file).
+    #[structopt(long, global = true, parse(from_os_str))]
+    template_dir: Option<PathBuf>,
+    /// Directory where the RFC repo is cloned (overrides the config file).
+    #[structopt(long, global = true, parse(from_os_str))]
+    working_dir: Option<PathBuf>,
+    /// URL of the RFC repo (overrides the config file).
+    #[structopt(long, global = true)]
+    git_url: Option<String>,
+    /// Branch of the RFC repo (overrides the config file).
+    #[structopt(long, global = true)]
+    git_branch: Option<String>,
+    /// Commit of the RFC repo to check out instead of the tip of the branch (overrides the config
+    /// file).
+    #[structopt(long, global = true)]
+    git_rev: Option<String>,
+    /// Directory for cached GitHub responses (overrides the config file).
+    #[structopt(long, global = true, parse(from_os_str))]
+    cache_dir: Option<PathBuf>,
+    /// The RFC repo on GitHub as `owner/repo` (overrides the config file).
+    #[structopt(long, global = true)]
+    github_repo: Option<String>,
+    /// Read PRs and issues from recorded fixtures in this directory instead of GitHub (overrides
+    /// the config file).
+    #[structopt(long, global = true, parse(from_os_str))]
+    fixture_dir: Option<PathBuf>,
+    /// How cached GitHub responses are used: `revalidate`, `offline`, or `off` (overrides the
+    /// config file).
+    #[structopt(long, global = true)]
+    github_cache: Option<CacheMode>,
+    /// Don't change any metadata, print the changes which would be made instead.
+    #[structopt(long, global = true)]
+    dry_run: bool,
+    /// Format of the changes printed by `--dry-run`: `plain` or `json` (one object per line).
+    #[structopt(long, global = true, default_value = "plain")]
+    diff_format: DiffFormat,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+impl Opts {
+    /// Load the config file and apply any overrides from the command line.
+    fn config(&self) -> Result<Config> {
+        let mut config = Config::load(self.config.as_deref())?;
+
+        macro_rules! set {
+            ($field: ident) => {
+                if let Some(f) = &self.$field {
+                    config.$field = f.clone();
+                }
+            };
+        }
+
+        set!(metadata_dir);
+        set!(out_dir);
+        set!(static_dir);
+        set!(template_dir);
+        set!(working_dir);
+        set!(git_url);
+        set!(git_branch);
+        set!(cache_dir);
+        set!(github_cache);
+        set!(github_repo);
+        if let Some(dir) = &self.fixture_dir {
+            config.fixture_dir = Some(dir.clone());
+        }
+        if let Some(rev) = &self.git_rev {
+            config.git_rev = Some(rev.clone());
+        }
+        if self.dry_run {
+            // `generate` writes the website, not metadata.
+            if let Command::Generate { .. } = self.command {
+                return Err(Error::DryRunUnsupported("generate"));
+            }
+            config.dry_run = Some(self.diff_format);
+        }
+        Ok(config)
+    }
+}
+
 #[derive(StructOpt)]
 enum Command {
     /// Add new metadata for an RFC.
@@ -91,9 +264,14 @@ enum Command {
     Get {
         /// Identify the RFC by number.
         number: u64,
-        /// Verbose output specifies the field and is escaped. Non-verbose prints only the queried value.
+        /// Verbose output names each field. Non-verbose prints only the queried values.
         #[structopt(short, long)]
         verbose: bool,
+        /// Output format: `plain`, `json`, `jsonl`, `csv`, or a template such as `{number}: {title}`.
+        /// If no fields are specified, all fields are printed (except for `plain`). A template names
+        /// its own fields.
+        #[structopt(long, default_value = "plain")]
+        format: Format,
         #[structopt(flatten)]
         flags: GetFlags,
     },
@@ -110,24 +288,66 @@ enum Command {
         /// Scan merged RFCs.
         #[structopt(long)]
         merged: bool,
+        /// Fetch the state of the tracking issues of accepted RFCs. Without `--force`, RFCs whose
+        /// tracking issues were all closed at the last scan are skipped.
+        #[structopt(long)]
+        issues: bool,
+        /// Read the history of merged RFCs (when they were merged and amended, and by whom) from the
+        /// RFC repo's commits, and fetch the PRs which amended them. With `--force`, PRs are
+        /// fetched again.
+        #[structopt(long)]
+        history: bool,
+        /// Find references between RFCs in the text of merged RFCs.
+        #[structopt(long)]
+        references: bool,
         /// Replace existing metadata.
         #[structopt(short, long)]
         force: bool,
+        #[structopt(flatten)]
+        repo: RepoFlags,
     },
     /// Emit stats about the metadata
     Stats,
     /// Generate the RFC website.
-    Generate,
+    Generate {
+        #[structopt(flatten)]
+        repo: RepoFlags,
+    },
+    /// Check metadata for consistency. Exits with a non-zero code if there are any problems.
+    Validate {
+        #[structopt(flatten)]
+        repo: RepoFlags,
+    },
     /// Query the metadata.
     Query {
+        /// Include RFCs which match the query, e.g., `team:lang and not tag:*`.
+        ///
+        /// Predicates have the form `field op value`. Fields are `number`, `filename`,
+        /// `start_date`, `feature_name`, `issues`, `title`, `teams`, `tags`, and `status`. `op` is
+        /// one of `:` (contains), `=` (equals), `<`, `<=`, `>`, or `>=`. The value `*` matches any
+        /// non-empty field. Predicates can be combined using `and`, `or`, `not`, and parentheses.
+        query: Option<Query>,
         /// Include RFCs which have the given tag. If no tag is specified, include RFCs with no tag.
         #[structopt(long)]
         tag: Option<Option<String>>,
+        /// Include RFCs which have the given status.
+        #[structopt(long)]
+        status: Option<Status>,
+        /// Output format: `plain`, `json`, `jsonl`, `csv`, or a template such as `{number}: {title}`.
+        #[structopt(long, default_value = "plain")]
+        format: Format,
+        /// Comma-separated fields to print. Defaults to `number` for `plain` output and all fields
+        /// otherwise. Not allowed with a template, which names its own fields.
+        #[structopt(long, use_delimiter = true)]
+        fields: Vec<String>,
     },
     /// Set/update tags on metadata
     Tag {
         /// Specify RFCs to update, uses all known RFCs if none are specified.
         numbers: Vec<u64>,
+        /// Only update RFCs which match the query (see `query`).
+        #[structopt(long)]
+        query: Option<Query>,
         /// Add a tag to the RFCs.
         #[structopt(long)]
         add: Option<String>,
@@ -146,11 +366,17 @@ enum Command {
         /// Attempt to infer the team from the tags for the specified RFCs.
         #[structopt(long)]
         infer_team: bool,
+        /// The RFC repo, used by `--init`.
+        #[structopt(flatten)]
+        repo: RepoFlags,
     },
     /// Set/update teams on metadata (see also `tag` command for some operations on teams and tags)
     Team {
         /// Specify RFCs to update, uses all known RFCs if none are specified.
         numbers: Vec<u64>,
+        /// Only update RFCs which match the query (see `query`).
+        #[structopt(long)]
+        query: Option<Query>,
         /// Add a team to the RFCs.
         #[structopt(long)]
         add: Option<String>,
@@ -161,22 +387,94 @@ enum Command {
         #[structopt(long)]
         replace: Option<String>,
     },
-    /// Migrate metadata between versions.
+    /// Edit the tag taxonomy (`tags.json`). Renaming, merging, and removing tags also updates the
+    /// RFCs which use them.
+    Tags {
+        #[structopt(subcommand)]
+        command: TagsCommand,
+    },
+    /// Migrate metadata to the current version. Use `--dry-run` to report what would be migrated.
     Migrate,
 }
 
+#[derive(StructOpt)]
+enum TagsCommand {
+    /// List the tags in the taxonomy, with the number of RFCs which use each.
+    List {
+        /// Only list tags of this team.
+        #[structopt(long)]
+        team: Option<String>,
+    },
+    /// Add a tag to the taxonomy.
+    Add {
+        tag: String,
+        /// A team the tag belongs to, can be given more than once.
+        #[structopt(long, required = true)]
+        team: Vec<String>,
+        #[structopt(long)]
+        name: Option<String>,
+        #[structopt(long)]
+        description: Option<String>,
+        /// A broader tag.
+        #[structopt(long)]
+        parent: Option<String>,
+    },
+    /// Remove a tag from the taxonomy.
+    Remove {
+        tag: String,
+        /// Also remove the tag from any RFCs which use it.
+        #[structopt(short, long)]
+        force: bool,
+    },
+    /// Rename a tag, keeping the old name as an alias.
+    Rename { from: String, to: String },
+    /// Set the team(s) a tag belongs to.
+    MoveToTeam {
+        tag: String,
+        #[structopt(required = true)]
+        teams: Vec<String>,
+    },
+    /// Merge a tag into another, which takes its aliases, teams, and RFCs.
+    Merge { from: String, into: String },
+}
+
+#[derive(StructOpt)]
+struct RepoFlags {
+    /// Read RFCs from an existing checkout of the RFC repo, rather than cloning or updating the
+    /// repo in the working directory. Does not use the network.
+    #[structopt(long, parse(from_os_str))]
+    rfc_repo: Option<PathBuf>,
+    /// Use the RFC repo in the working directory without updating it.
+    #[structopt(long)]
+    offline: bool,
+}
+
+impl From<RepoFlags> for RfcRepo {
+    fn from(flags: RepoFlags) -> RfcRepo {
+        match flags.rfc_repo {
+            Some(path) => RfcRepo::Local(path),
+            None if flags.offline => RfcRepo::WorkingOffline,
+            None => RfcRepo::Working,
+        }
+    }
+}
+
 #[derive(StructOpt)]
 struct AddFlags {
     #[structopt(long)]
     filename: String,
+    /// Formatted as `YYYY-MM-DD`.
     #[structopt(long)]
-    start_date: String,
+    start_date: NaiveDate,
     #[structopt(long)]
     feature_name: Option<String>,
+    /// Tracking issues, e.g., `rust-lang/rust#1234`, `#1234`, or a GitHub URL.
     #[structopt(long)]
     issues: Option<String>,
     #[structopt(long)]
     title: Option<String>,
+    #[structopt(long)]
+    status: Option<Status>,
     // TODO tags/teams
 }
 
@@ -184,14 +482,18 @@ struct AddFlags {
 struct SetFlags {
     #[structopt(long)]
     filename: Option<String>,
+    /// Formatted as `YYYY-MM-DD`.
     #[structopt(long)]
-    start_date: Option<String>,
+    start_date: Option<NaiveDate>,
     #[structopt(long)]
     feature_name: Option<String>,
+    /// Tracking issues, e.g., `rust-lang/rust#1234`, `#1234`, or a GitHub URL.
     #[structopt(long)]
     issues: Option<String>,
     #[structopt(long)]
     title: Option<String>,
+    #[structopt(long)]
+    status: Option<Status>,
     // TODO tags/teams
 }
 
@@ -211,6 +513,32 @@ struct GetFlags {
     teams: bool,
     #[structopt(long)]
     tags: bool,
+    #[structopt(long)]
+    status: bool,
+}
+
+impl GetFlags {
+    /// The names of the fields which should be printed.
+    fn fields(&self) -> Vec<String> {
+        let mut result = Vec::new();
+        macro_rules! field {
+            ($field: ident) => {
+                if self.$field {
+                    result.push(stringify!($field).to_owned());
+                }
+            };
+        }
+
+        field!(filename);
+        field!(start_date);
+        field!(feature_name);
+        field!(issues);
+        field!(title);
+        field!(teams);
+        field!(tags);
+        field!(status);
+        result
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -241,6 +569,7 @@ impl FromStr for TagScanFlags {
 enum ExitCode {
     Other = 1,
     MissingMetadata = 2,
+    Invalid = 3,
 }
 
 fn run_add(number: u64, force: bool, flags: AddFlags) {
@@ -257,19 +586,31 @@ fn add_metadata(number: u64, force: bool, flags: AddFlags) -> Result<()> {
         }
     }
 
-    let mut metadata = RfcMetadata::new(number, flags.filename, flags.start_date);
+    let mut metadata = RfcMetadata::new(number, flags.filename, Some(flags.start_date));
 
     if let Some(s) = flags.feature_name {
         metadata.feature_name = parse_multiple(&s);
     }
     if let Some(s) = flags.issues {
-        metadata.issues = parse_multiple(&s);
+        metadata.issues = parse_issues(&s)?;
     }
     metadata.title = flags.title;
+    if let Some(s) = flags.status {
+        metadata.status = s;
+    }
 
     save_metadata(&metadata)
 }
 
+/// Parse a list of issue references, e.g., `rust-lang/rust#1234, #5678`.
+fn parse_issues(input: &str) -> Result<Vec<IssueRef>> {
+    input
+        .split([' ', ',', ';'])
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse())
+        .collect()
+}
+
 fn parse_multiple(input: &str) -> Vec<String> {
     fn unquote(s: String) -> String {
         if (s.starts_with('`') || s.starts_with('"') || s.starts_with('\''))
@@ -353,22 +694,27 @@ fn set_metadata(number: u64, flags: SetFlags) -> Result<()> {
         metadata.filename = f;
     }
     if let Some(f) = flags.start_date {
-        metadata.start_date = f;
+        metadata.start_date = Some(f);
     }
     if let Some(s) = flags.feature_name {
         metadata.feature_name = parse_multiple(&s);
     }
     if let Some(s) = flags.issues {
-        metadata.issues = parse_multiple(&s);
+        metadata.issues = parse_issues(&s)?;
+        let issues = &metadata.issues;
+        metadata.issue_status.retain(|s| issues.contains(&s.issue));
     }
     if let Some(f) = flags.title {
         metadata.title = Some(f);
     }
+    if let Some(s) = flags.status {
+        metadata.status = s;
+    }
 
     save_metadata(&metadata)
 }
 
-fn run_get(number: u64, verbose: bool, flags: GetFlags) {
+fn run_get(number: u64, verbose: bool, format: Format, flags: GetFlags) {
     let metadata = match open_metadata(number) {
         Ok(m) => m,
         Err(Error::FileNotFound) => {
@@ -381,72 +727,20 @@ fn run_get(number: u64, verbose: bool, flags: GetFlags) {
         }
     };
 
-    // Print a single field of metadata
-    macro_rules! render {
-        ($field: ident) => {
-            if flags.$field {
-                if verbose {
-                    println!("{}: {:?}", stringify!($field), metadata.$field);
-                } else {
-                    println!("{}", metadata.$field);
-                }
-            }
-        };
-    }
-    macro_rules! render_opt {
-        ($field: ident) => {
-            if flags.$field {
-                match metadata.$field {
-                    Some(f) => {
-                        if verbose {
-                            println!("{}: {:?}", stringify!($field), f);
-                        } else {
-                            println!("{}", f);
-                        }
-                    }
-                    None => {
-                        if verbose {
-                            println!("{}:", stringify!($field));
-                        }
-                    }
-                }
-            }
-        };
-    }
-    macro_rules! render_vec {
-        ($field: ident) => {
-            if flags.$field {
-                let iter = metadata
-                    .$field
-                    .iter()
-                    .map(|i| {
-                        if verbose {
-                            // TODO impl Display for Tag and use {}
-                            format!("{:?}", i)
-                        } else {
-                            format!("{:?}", i)
-                        }
-                    })
-                    .intersperse(", ".to_owned());
-
-                if verbose {
-                    print!("{}: [", stringify!($field));
-                } else {
-                    print!("[");
-                }
-                iter.for_each(|s| print!("{}", s));
-                println!("]");
-            }
-        };
+    let mut fields = flags.fields();
+    let result = output::check_fields(&format, &fields).and_then(|_| {
+        if format == Format::Plain {
+            return output::print_fields(&metadata, &fields, verbose);
+        }
+        if fields.is_empty() {
+            fields = output::all_fields()?;
+        }
+        output::print_one(&metadata, &format, &fields)
+    });
+    if let Err(e) = result {
+        eprintln!("Error: {:?}", e);
+        process::exit(ExitCode::Other as i32);
     }
-
-    render!(filename);
-    render!(start_date);
-    render_vec!(feature_name);
-    render_vec!(issues);
-    render_opt!(title);
-    render_vec!(teams);
-    render_vec!(tags);
 }
 
 fn run_delete(number: u64) {
@@ -463,12 +757,59 @@ fn run_delete(number: u64) {
     }
 }
 
-fn run_scan_open(_force: bool) {
-    unimplemented!();
+fn run_scan_open(force: bool) {
+    if let Err(e) = scan_open(force) {
+        eprintln!("Error: {:?}", e);
+        process::exit(ExitCode::Other as i32);
+    }
 }
 
-fn run_scan_merged(force: bool) {
-    match scan_merged(force) {
+fn scan_open(force: bool) -> Result<()> {
+    let forge = forge();
+    let gh_data = get_open_rfc_metadata(&*forge)?;
+    let open_numbers: Vec<u64> = gh_data.iter().map(|d| d.number).collect();
+    let tag_metadata = read_tag_metadata()?;
+    let team_metadata = read_team_metadata()?;
+    let mut transaction = Transaction::new();
+    for datum in gh_data {
+        if force || metadata_exists(datum.number).is_err() {
+            let mut metadata: RfcMetadata = datum.clone().try_into()?;
+            update_from_open_pr(
+                &mut metadata,
+                &datum,
+                &tag_metadata,
+                &team_metadata,
+                UpdateOptions::all(),
+            );
+            transaction.save_metadata(&metadata)?;
+        } else {
+            // Keep the status of known proposals up to date (e.g., entering FCP).
+            let mut metadata = open_metadata(datum.number)?;
+            let status = RfcMetadata::try_from(datum)?.status;
+            if metadata.status.is_open() && metadata.status != status {
+                metadata.status = status;
+                transaction.save_metadata(&metadata)?;
+            }
+        }
+    }
+
+    // Proposals which are no longer open have been merged, closed, or postponed.
+    let closed: Vec<RfcMetadata> = all_metadata()?
+        .into_iter()
+        .filter(|m| m.status.is_open() && !open_numbers.contains(&m.number))
+        .collect();
+    let numbers: Vec<u64> = closed.iter().map(|m| m.number).collect();
+    let status = get_pr_status(&*forge, &numbers)?;
+    for mut metadata in closed {
+        metadata.status = status[&metadata.number];
+        transaction.save_metadata(&metadata)?;
+    }
+
+    transaction.commit()
+}
+
+fn run_scan_merged(repo: &RfcRepo, force: bool) {
+    match scan_merged(repo, force) {
         Err(e) => {
             eprintln!("Error: {:?}", e);
             process::exit(ExitCode::Other as i32);
@@ -477,21 +818,122 @@ fn run_scan_merged(force: bool) {
     }
 }
 
-fn scan_merged(force: bool) -> Result<()> {
-    let gh_data = get_merged_rfc_metadata()?;
+fn scan_merged(repo: &RfcRepo, force: bool) -> Result<()> {
+    let gh_data = get_merged_rfc_metadata(repo)?;
     let tag_metadata = read_tag_metadata()?;
+    let team_metadata = read_team_metadata()?;
+    let mut transaction = Transaction::new();
+    let mut new = Vec::new();
     for datum in gh_data {
         let number = datum.number()?;
         if force || metadata_exists(number).is_err() {
-            let mut metadata: RfcMetadata = datum.try_into()?;
-            update_from_pr(&mut metadata, &tag_metadata, UpdateOptions::all())?;
-            save_metadata(&metadata)?;
+            new.push(datum.try_into()?);
+        } else {
+            // A proposal we already know about has been merged.
+            let mut metadata = open_metadata(number)?;
+            if metadata.status.is_open() {
+                metadata.status = Status::Merged;
+                metadata.filename = datum.filename().to_owned();
+                transaction.save_metadata(&metadata)?;
+            }
         }
-        // Progress indicator
-        eprint!(".");
     }
 
-    Ok(())
+    let numbers: Vec<u64> = new.iter().map(|m: &RfcMetadata| m.number).collect();
+    let labels = get_pr_labels(&*forge(), &numbers)?;
+    for mut metadata in new {
+        let labels = &labels[&metadata.number];
+        update_from_pr(
+            &mut metadata,
+            labels,
+            &tag_metadata,
+            &team_metadata,
+            UpdateOptions::all(),
+        );
+        transaction.save_metadata(&metadata)?;
+    }
+
+    transaction.commit()
+}
+
+fn run_scan_issues(force: bool) {
+    if let Err(e) = scan_issues(force) {
+        eprintln!("Error: {:?}", e);
+        process::exit(ExitCode::Other as i32);
+    }
+}
+
+fn scan_issues(force: bool) -> Result<()> {
+    let mut to_scan = Vec::new();
+    for metadata in all_metadata()? {
+        if !metadata.status.is_accepted() || metadata.issues.is_empty() {
+            continue;
+        }
+        // Closed issues are rarely reopened, so don't rescan unless something has changed.
+        let scanned: Vec<_> = metadata.issue_status.iter().map(|s| &s.issue).collect();
+        let issues: Vec<_> = metadata.issues.iter().collect();
+        if !force && scanned == issues && metadata.issue_status.iter().all(|s| !s.open) {
+            continue;
+        }
+        to_scan.push(metadata);
+    }
+
+    let mut issues: Vec<IssueRef> = to_scan.iter().flat_map(|m| m.issues.clone()).collect();
+    issues.sort_by_key(|i| i.to_string());
+    issues.dedup();
+    let status = forge().get_issues(issues)?;
+
+    let mut transaction = Transaction::new();
+    for mut metadata in to_scan {
+        metadata.issue_status = metadata
+            .issues
+            .iter()
+            .filter_map(|i| status.get(i).cloned())
+            .collect();
+        transaction.save_metadata(&metadata)?;
+    }
+
+    transaction.commit()
+}
+
+fn run_scan_history(repo: &RfcRepo, force: bool) {
+    if let Err(e) = scan_history(repo, force) {
+        eprintln!("Error: {:?}", e);
+        process::exit(ExitCode::Other as i32);
+    }
+}
+
+fn scan_history(repo: &RfcRepo, force: bool) -> Result<()> {
+    let history = rfc_history(&repo.root()?)?;
+    let mut metadata = all_metadata()?;
+    let changed = update_history(&*forge(), &mut metadata, history, force)?;
+
+    let mut transaction = Transaction::new();
+    for m in metadata.iter().filter(|m| changed.contains(&m.number)) {
+        transaction.save_metadata(m)?;
+    }
+    transaction.commit()
+}
+
+fn run_scan_references(repo: &RfcRepo) {
+    if let Err(e) = scan_references(repo) {
+        eprintln!("Error: {:?}", e);
+        process::exit(ExitCode::Other as i32);
+    }
+}
+
+fn scan_references(repo: &RfcRepo) -> Result<()> {
+    let texts = get_merged_rfc_data(repo)?
+        .into_iter()
+        .map(|d| (d.number, d.text))
+        .collect();
+    let mut metadata = all_metadata()?;
+    // References and `referenced_by` must agree, so save them together.
+    let mut transaction = Transaction::new();
+    for m in update_references(&mut metadata, &texts) {
+        transaction.save_metadata(m)?;
+    }
+    transaction.commit()
 }
 
 fn run_stats() {
@@ -507,6 +949,7 @@ fn run_stats() {
     let title = metadata.iter().filter(|m| m.title.is_some()).count();
     let one_tag = metadata.iter().filter(|m| !m.tags.is_empty()).count();
     let teams = metadata.iter().filter(|m| !m.teams.is_empty()).count();
+    let start_date = metadata.iter().filter(|m| m.start_date.is_some()).count();
 
     println!("Total RFCs indexed: {}", total);
     println!(
@@ -524,10 +967,49 @@ fn run_stats() {
         one_tag,
         (one_tag as f64 / total as f64) * 100.0
     );
+    println!(
+        "RFCs with a start date: {} ({:.1}%)",
+        start_date,
+        (start_date as f64 / total as f64) * 100.0
+    );
+
+    let today = Local::today().naive_local();
+    let accepted = metadata.iter().filter(|m| m.status.is_accepted()).count();
+    println!("Implementation of accepted RFCs (from tracking issues):");
+    for p in Progress::ALL {
+        let count = metadata
+            .iter()
+            .filter(|m| m.progress(today) == Some(p))
+            .count();
+        println!(
+            "  {}: {} ({:.1}%)",
+            p,
+            count,
+            (count as f64 / accepted as f64) * 100.0
+        );
+    }
+    let unknown = metadata
+        .iter()
+        .filter(|m| m.status.is_accepted() && m.progress(today).is_none())
+        .count();
+    println!(
+        "  unknown: {} ({:.1}%)",
+        unknown,
+        (unknown as f64 / accepted as f64) * 100.0
+    );
+
+    let mut by_year = BTreeMap::new();
+    for d in metadata.iter().filter_map(|m| m.start_date) {
+        *by_year.entry(d.year()).or_insert(0) += 1;
+    }
+    println!("RFCs started by year:");
+    for (year, count) in by_year {
+        println!("  {}: {}", year, count);
+    }
 }
 
-fn run_generate() {
-    match generate::generate() {
+fn run_generate(repo: RfcRepo) {
+    match generate::generate(&repo) {
         Ok(_) => {}
         Err(e) => {
             eprintln!("Error: {:?}", e);
@@ -536,7 +1018,31 @@ fn run_generate() {
     }
 }
 
-fn run_query(tag: Option<Option<String>>) {
+fn run_validate(repo: RfcRepo) {
+    let problems = match validate::validate(&repo) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            process::exit(ExitCode::Other as i32);
+        }
+    };
+
+    for p in &problems {
+        println!("{}", p);
+    }
+    if !problems.is_empty() {
+        eprintln!("{} problems found", problems.len());
+        process::exit(ExitCode::Invalid as i32);
+    }
+}
+
+fn run_query(
+    query: Option<Query>,
+    tag: Option<Option<String>>,
+    status: Option<Status>,
+    format: Format,
+    mut fields: Vec<String>,
+) {
     let mut metadata = match all_metadata() {
         Ok(m) => m,
         Err(e) => {
@@ -545,6 +1051,15 @@ fn run_query(tag: Option<Option<String>>) {
         }
     };
 
+    if let Some(query) = query {
+        if let Err(e) = read_team_metadata().and_then(|teams| query.check_teams(&teams)) {
+            eprintln!("Error: {:?}", e);
+            process::exit(ExitCode::Other as i32);
+        }
+        let matching = query.matching(&metadata);
+        metadata.retain(|d| matching.contains(&d.number));
+    }
+
     if let Some(tag) = tag {
         match tag {
             Some(tag) => {
@@ -557,24 +1072,43 @@ fn run_query(tag: Option<Option<String>>) {
         }
     }
 
+    if let Some(status) = status {
+        metadata.retain(|d| d.status == status);
+    }
+
     metadata.sort();
-    for m in metadata {
-        // FIXME data printed should be specified by the query
-        print!("{} ", m.number);
+
+    if let Err(e) = output::check_fields(&format, &fields) {
+        eprintln!("Error: {:?}", e);
+        process::exit(ExitCode::Other as i32);
+    }
+    if fields.is_empty() {
+        fields = match format {
+            Format::Plain => vec!["number".to_owned()],
+            _ => output::all_fields().unwrap(),
+        };
+    }
+    if let Err(e) = output::print_all(&metadata, &format, &fields) {
+        eprintln!("Error: {:?}", e);
+        process::exit(ExitCode::Other as i32);
     }
-    println!();
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_tag(
     numbers: Vec<u64>,
+    query: Option<Query>,
     add: Option<String>,
     scan: Option<Option<TagScanFlags>>,
     init: bool,
     remove: Option<String>,
     replace: Option<String>,
     infer_team: bool,
+    repo: RfcRepo,
 ) {
-    match tag(numbers, add, scan, init, remove, replace, infer_team) {
+    match tag(
+        numbers, query, add, scan, init, remove, replace, infer_team, repo,
+    ) {
         Err(e) => {
             eprintln!("Error: {:?}", e);
             process::exit(ExitCode::Other as i32);
@@ -583,32 +1117,57 @@ fn run_tag(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn tag(
-    mut numbers: Vec<u64>,
+    numbers: Vec<u64>,
+    query: Option<Query>,
     add: Option<String>,
     scan: Option<Option<TagScanFlags>>,
     init: bool,
     remove: Option<String>,
     replace: Option<String>,
     infer_team: bool,
+    repo: RfcRepo,
 ) -> Result<()> {
     if init {
-        return tag_init();
+        return tag_init(&repo);
     }
 
-    if numbers.is_empty() {
-        numbers = all_metadata_numbers()?;
-    }
+    let numbers = select_rfcs(numbers, query.as_ref())?;
 
     let scan = scan.map(|s| s.unwrap_or_default());
 
-    let tag_metadata = if scan.is_some() || infer_team {
+    let tag_metadata = if scan.is_some() || infer_team || add.is_some() || replace.is_some() {
         Some(read_tag_metadata()?)
     } else {
         None
     };
+    let team_metadata = match scan {
+        Some(_) => Some(read_team_metadata()?),
+        None => None,
+    };
+    // Use the canonical tag for aliases and deprecated tags.
+    let resolve = |name: &str| -> String {
+        match tag_metadata.as_ref().unwrap().resolve(name) {
+            Some(tag) => {
+                if tag != name {
+                    eprintln!("info: using tag `{}` for `{}`", tag, name);
+                }
+                tag.to_owned()
+            }
+            None => {
+                eprintln!("warning: tag `{}` is not in the tag metadata", name);
+                name.to_owned()
+            }
+        }
+    };
+    let add = add.map(|a| resolve(&a));
+    let replace = replace
+        .map(|r| parse_replace(&r).map(|(from, to)| (from.to_owned(), resolve(to))))
+        .transpose()?;
 
     // eprintln!("info: tagging {}", numbers.len());
+    let mut all = Vec::with_capacity(numbers.len());
     for n in numbers {
         let mut metadata = open_metadata(n)?;
         if let Some(add) = &add {
@@ -621,61 +1180,76 @@ fn tag(
             metadata.tags.retain(|t| t != remove);
         }
 
-        if let Some(replace) = &replace {
-            let mut splits = replace.split('/');
-            let from = splits
-                .next()
-                .expect("No tag to replace, use syntax `from/to`");
-            let to = splits
-                .next()
-                .expect("No tag to replace with, use syntax `from/to`");
-            assert!(
-                splits.next().is_none(),
-                "Too many arguments to `tag --replace`"
-            );
-
+        if let Some((from, to)) = &replace {
             for t in &mut metadata.tags {
                 if t == from {
-                    *t = to.to_owned();
+                    *t = to.clone();
                 }
             }
             metadata.tags.dedup();
         }
 
-        if let Some(scan) = scan {
-            let opts = UpdateOptions {
-                tags: metadata.tags.is_empty() || scan == TagScanFlags::All,
-                teams: metadata.teams.is_empty() || scan == TagScanFlags::All,
-            };
+        all.push(metadata);
+    }
 
-            update_from_pr(&mut metadata, tag_metadata.as_ref().unwrap(), opts)?;
+    let scan_opts = |metadata: &RfcMetadata| {
+        scan.map(|scan| UpdateOptions {
+            tags: metadata.tags.is_empty() || scan == TagScanFlags::All,
+            teams: metadata.teams.is_empty() || scan == TagScanFlags::All,
+        })
+        .filter(|opts| opts.tags || opts.teams)
+    };
+    // Fetch all the PRs we need at once, since fetching concurrently is much faster.
+    let to_scan: Vec<u64> = all
+        .iter()
+        .filter(|m| scan_opts(m).is_some())
+        .map(|m| m.number)
+        .collect();
+    let labels = get_pr_labels(&*forge(), &to_scan)?;
+
+    let mut transaction = Transaction::new();
+    for mut metadata in all {
+        if let Some(opts) = scan_opts(&metadata) {
+            let labels = &labels[&metadata.number];
+            update_from_pr(
+                &mut metadata,
+                labels,
+                tag_metadata.as_ref().unwrap(),
+                team_metadata.as_ref().unwrap(),
+                opts,
+            );
         }
 
         if infer_team {
             infer_team_from_tags(&mut metadata, tag_metadata.as_ref().unwrap())?;
         }
 
-        save_metadata(&metadata)?;
-
-        // Progress indicator
-        eprint!(".");
+        transaction.save_metadata(&metadata)?;
     }
 
-    Ok(())
+    transaction.commit()
 }
 
-fn tag_init() -> Result<()> {
-    let data = init_tag_metadata()?;
-    write_tag_metadata(data)
+fn tag_init(repo: &RfcRepo) -> Result<()> {
+    let data = init_tag_metadata(repo, &*forge(), &read_team_metadata()?)?;
+    // Keep the existing taxonomy, adding any new tags.
+    let mut tags = if tag_metadata_exists() {
+        read_tag_metadata()?.tags
+    } else {
+        Vec::new()
+    };
+    add_team_tags(&mut tags, data);
+    write_tag_metadata(&tags)
 }
 
 fn run_team(
     numbers: Vec<u64>,
+    query: Option<Query>,
     add: Option<String>,
     remove: Option<String>,
     replace: Option<String>,
 ) {
-    match team(numbers, add, remove, replace) {
+    match team(numbers, query, add, remove, replace) {
         Err(e) => {
             eprintln!("Error: {:?}", e);
             process::exit(ExitCode::Other as i32);
@@ -685,70 +1259,173 @@ fn run_team(
 }
 
 fn team(
-    mut numbers: Vec<u64>,
+    numbers: Vec<u64>,
+    query: Option<Query>,
     add: Option<String>,
     remove: Option<String>,
     replace: Option<String>,
 ) -> Result<()> {
-    if numbers.is_empty() {
-        numbers = all_metadata_numbers()?;
-    }
+    let numbers = select_rfcs(numbers, query.as_ref())?;
+    let team_metadata = read_team_metadata()?;
+    let add = add.map(|t| team_metadata.parse(&t)).transpose()?;
+    let remove = remove.map(|t| team_metadata.parse(&t)).transpose()?;
+    let replace = match replace {
+        Some(r) => {
+            let (from, to) = parse_replace(&r)?;
+            Some((team_metadata.parse(from)?, team_metadata.parse(to)?))
+        }
+        None => None,
+    };
 
     // eprintln!("info: tagging {}", numbers.len());
+    let mut transaction = Transaction::new();
     for n in numbers {
         let mut metadata = open_metadata(n)?;
         if let Some(add) = &add {
-            let team: Team = add.parse()?;
-            if !metadata.teams.contains(&team) {
-                metadata.teams.push(team);
+            if !metadata.teams.contains(add) {
+                metadata.teams.push(add.clone());
             }
         }
 
         if let Some(remove) = &remove {
-            let team: Team = remove.parse()?;
-            metadata.teams.retain(|t| *t != team);
-        }
-
-        if let Some(replace) = &replace {
-            let mut splits = replace.split('/');
-            let from: Team = splits
-                .next()
-                .expect("No team to replace, use syntax `from/to`")
-                .parse()?;
-            let to: Team = splits
-                .next()
-                .expect("No team to replace with, use syntax `from/to`")
-                .parse()?;
-            assert!(
-                splits.next().is_none(),
-                "Too many arguments to `team --replace`"
-            );
+            metadata.teams.retain(|t| t != remove);
+        }
 
+        if let Some((from, to)) = &replace {
             for t in &mut metadata.teams {
-                if *t == from {
-                    *t = to;
+                if t == from {
+                    *t = to.clone();
                 }
             }
             metadata.teams.dedup();
         }
 
-        save_metadata(&metadata)?;
+        transaction.save_metadata(&metadata)?;
+    }
+
+    transaction.commit()
+}
+
+/// Parse the `from/to` argument of `--replace`.
+fn parse_replace(replace: &str) -> Result<(&str, &str)> {
+    match replace.split_once('/') {
+        Some((from, to)) if !from.is_empty() && !to.is_empty() && !to.contains('/') => {
+            Ok((from, to))
+        }
+        _ => Err(Error::ParseArg(format!(
+            "`{}`, use syntax `from/to`",
+            replace
+        ))),
+    }
+}
 
-        // Progress indicator
-        eprint!(".");
+fn run_tags(command: TagsCommand) {
+    if let Err(e) = tags(command) {
+        eprintln!("Error: {:?}", e);
+        process::exit(ExitCode::Other as i32);
     }
+}
+
+fn tags(command: TagsCommand) -> Result<()> {
+    let (mut tags, _) = read_tags()?;
+    let mut all = all_metadata()?;
+    let team_metadata = read_team_metadata()?;
+
+    let changed = match command {
+        TagsCommand::List { team } => {
+            let team = team.map(|t| team_metadata.parse(&t)).transpose()?;
+            tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+            for t in &tags {
+                if team.as_ref().is_some_and(|team| !t.teams.contains(team)) {
+                    continue;
+                }
+                let count = all.iter().filter(|m| m.tags.contains(&t.tag)).count();
+                let teams: Vec<_> = t.teams.iter().map(|t| t.to_string()).collect();
+                print!(
+                    "{} ({}): {} RFCs [{}]",
+                    t.tag,
+                    t.name(),
+                    count,
+                    teams.join(", ")
+                );
+                if let Some(d) = &t.deprecated {
+                    print!(" deprecated, use {}", d);
+                }
+                println!();
+            }
+            return Ok(());
+        }
+        TagsCommand::Add {
+            tag,
+            team,
+            name,
+            description,
+            parent,
+        } => {
+            let teams = team
+                .iter()
+                .map(|t| team_metadata.parse(t))
+                .collect::<Result<_>>()?;
+            let mut tag = Tag::new(tag, teams);
+            tag.name = name;
+            tag.description = description;
+            tag.parent = parent;
+            taxonomy::add(&mut tags, tag)?;
+            Vec::new()
+        }
+        TagsCommand::Remove { tag, force } => taxonomy::remove(&mut tags, &mut all, &tag, force)?,
+        TagsCommand::Rename { from, to } => taxonomy::rename(&mut tags, &mut all, &from, &to)?,
+        TagsCommand::MoveToTeam { tag, teams } => {
+            let teams = teams
+                .iter()
+                .map(|t| team_metadata.parse(t))
+                .collect::<Result<_>>()?;
+            taxonomy::move_to_team(&mut tags, &tag, teams)?;
+            Vec::new()
+        }
+        TagsCommand::Merge { from, into } => taxonomy::merge(&mut tags, &mut all, &from, &into)?,
+    };
 
+    let mut transaction = Transaction::new();
+    transaction.write_tag_metadata(&tags)?;
+    for m in all.iter().filter(|m| changed.contains(&m.number)) {
+        transaction.save_metadata(m)?;
+    }
+    transaction.commit()?;
+    if !changed.is_empty() {
+        let updated = match config().dry_run {
+            Some(_) => "would be updated",
+            None => "updated",
+        };
+        eprintln!("info: {} RFCs {}", changed.len(), updated);
+    }
     Ok(())
 }
 
-fn run_migrate() {
-    let _metadata = match all_metadata() {
-        Ok(m) => m,
-        Err(e) => {
-            eprintln!("Error: {:?}", e);
-            process::exit(ExitCode::Other as i32);
-        }
+/// The RFCs a bulk command should operate on: the given numbers (or all RFCs if none are given),
+/// restricted to those matching `query`.
+fn select_rfcs(numbers: Vec<u64>, query: Option<&Query>) -> Result<Vec<u64>> {
+    let query = match query {
+        Some(q) => q,
+        None if numbers.is_empty() => return all_metadata_numbers(),
+        None => return Ok(numbers),
     };
+    query.check_teams(&read_team_metadata()?)?;
+
+    let mut result: Vec<u64> = query
+        .matching(&all_metadata()?)
+        .into_iter()
+        .filter(|n| numbers.is_empty() || numbers.contains(n))
+        .collect();
+    result.sort();
+    Ok(result)
+}
+
+fn run_migrate() {
+    if let Err(e) = migrate::migrate_all() {
+        eprintln!("Error: {:?}", e);
+        process::exit(ExitCode::Other as i32);
+    }
 }
 
 #[cfg(test)]
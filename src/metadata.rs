@@ -1,34 +1,50 @@
-use crate::errors::{Error, Result};
+use crate::{
+    config::config,
+    diff::file_changes,
+    errors::{Error, Result},
+    migrate::upgrade,
+};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::{
     cmp::Ordering,
     collections::HashMap,
     fmt,
     fs::{self, File},
-    io::{Read, Write},
+    io::{self, Read, Write},
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-const METADATA_VERSION: u64 = 1;
-const METADATA_DIR: &str = "metadata";
+pub const METADATA_VERSION: u64 = 10;
 const TAG_METADATA_FILENAME: &str = "tags.json";
+const TEAM_METADATA_FILENAME: &str = "teams.json";
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RfcMetadata {
     pub version: u64,
     pub number: u64,
     pub filename: String,
-    pub start_date: String,
+    pub start_date: Option<NaiveDate>,
     pub feature_name: Vec<String>,
-    pub issues: Vec<String>,
+    pub issues: Vec<IssueRef>,
     pub title: Option<String>,
     pub teams: Vec<Team>,
     pub tags: Vec<String>,
+    pub status: Status,
+    /// The state of each of `issues`, as of the last `scan --issues`.
+    pub issue_status: Vec<IssueStatus>,
+    /// The history of the RFC's text in the RFC repo, as of the last `scan --history`. `None` if
+    /// the RFC has not been merged or not scanned.
+    pub history: Option<History>,
+    /// The RFCs which this RFC's text refers to, as of the last `scan --references`.
+    pub references: Vec<u64>,
+    /// The RFCs whose text refers to this RFC, the inverse of `references`.
+    pub referenced_by: Vec<u64>,
 }
 
 impl RfcMetadata {
-    pub fn new(number: u64, filename: String, start_date: String) -> RfcMetadata {
+    pub fn new(number: u64, filename: String, start_date: Option<NaiveDate>) -> RfcMetadata {
         RfcMetadata {
             version: METADATA_VERSION,
             number,
@@ -39,8 +55,35 @@ impl RfcMetadata {
             title: None,
             teams: Vec::new(),
             tags: Vec::new(),
+            status: Status::Merged,
+            issue_status: Vec::new(),
+            history: None,
+            references: Vec::new(),
+            referenced_by: Vec::new(),
         }
     }
+
+    /// How far implementation has got, judged by the state of the RFC's tracking issues. `None`
+    /// if the RFC has not been accepted or its tracking issues have not been scanned.
+    pub fn progress(&self, today: NaiveDate) -> Option<Progress> {
+        if !self.status.is_accepted() || self.issue_status.is_empty() {
+            return None;
+        }
+
+        let open: Vec<_> = self.issue_status.iter().filter(|i| i.open).collect();
+        if open.is_empty() {
+            return Some(Progress::Implemented);
+        }
+        let stalled = open.iter().all(|i| {
+            i.labels.iter().any(|l| STALLED_LABELS.contains(&&**l))
+                || (today - i.updated).num_days() > STALLED_DAYS
+        });
+        Some(if stalled {
+            Progress::Stalled
+        } else {
+            Progress::Ongoing
+        })
+    }
 }
 
 impl PartialEq for RfcMetadata {
@@ -63,132 +106,926 @@ impl Ord for RfcMetadata {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Hash)]
-pub enum Team {
-    Lang,
-    Libs,
-    Core,
-    Tools,
-    Compiler,
-    Docs,
+/// Parse a date in any of the formats found in the start dates of RFCs, e.g., `2015-1-30`,
+/// `23-07-2014`, `01 March, 2016`, or `(fill me in with today's date, 2014-07-17)`.
+pub fn parse_date(s: &str) -> Option<NaiveDate> {
+    const FORMATS: &[&str] = &[
+        "%Y-%m-%d",
+        "%Y/%m/%d",
+        "%d-%m-%Y",
+        "%d/%m/%Y",
+        "%d %B, %Y",
+        "%d %B %Y",
+        "%B %d, %Y",
+    ];
+
+    let s = s
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches(')')
+        .trim();
+    // Try the whole string, then each comma-separated part (for "(fill me in with today's date, ...)").
+    std::iter::once(s)
+        .chain(s.split(',').map(|p| p.trim()))
+        .find_map(|candidate| {
+            FORMATS
+                .iter()
+                .find_map(|f| NaiveDate::parse_from_str(candidate, f).ok())
+        })
+}
+
+/// The repo of tracking issues which are written as just `#1234`.
+const DEFAULT_ISSUE_REPO: &str = "rust-lang/rust";
+
+/// A reference to a GitHub issue or PR, written canonically as `owner/repo#1234`.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct IssueRef {
+    /// `owner/repo`.
+    pub repo: String,
+    pub number: u64,
 }
 
-impl FromStr for Team {
+impl IssueRef {
+    pub fn url(&self) -> String {
+        // GitHub redirects to the PR if the number is a PR.
+        format!("https://github.com/{}/issues/{}", self.repo, self.number)
+    }
+
+    /// Find all issue references in free text such as the `Rust Issue` field of an RFC header.
+    /// Accepts markdown links (the URL wins over the link text), URLs, `owner/repo#1234`, and
+    /// `#1234`. Anything else is ignored.
+    pub fn parse_all(text: &str) -> Vec<IssueRef> {
+        let mut result = Vec::new();
+        for word in text.split(|c: char| c.is_whitespace() || c == ',' || c == ';') {
+            if word.contains("github.com/") {
+                result.extend(word.split("github.com/").skip(1).filter_map(from_url));
+            } else if let Some(r) = from_short(word) {
+                result.push(r);
+            }
+        }
+        result.dedup();
+        result
+    }
+}
+
+/// Parse the part of a GitHub URL after `github.com/`, e.g., `rust-lang/rust/issues/1234)`.
+fn from_url(path: &str) -> Option<IssueRef> {
+    let mut parts = path.split('/');
+    let owner = parts.next().filter(|s| !s.is_empty())?;
+    let repo = parts.next().filter(|s| !s.is_empty())?;
+    if !matches!(parts.next()?, "issues" | "pull") {
+        return None;
+    }
+    Some(IssueRef {
+        repo: format!("{}/{}", owner, repo),
+        number: leading_number(parts.next()?)?,
+    })
+}
+
+/// Parse `#1234` or `owner/repo#1234`, possibly with surrounding punctuation or markdown.
+fn from_short(word: &str) -> Option<IssueRef> {
+    let (repo, number) = word.split_once('#')?;
+    let repo = repo.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let repo = if repo.is_empty() {
+        DEFAULT_ISSUE_REPO.to_owned()
+    } else if repo.split('/').count() == 2 {
+        repo.to_owned()
+    } else {
+        return None;
+    };
+    Some(IssueRef {
+        repo,
+        number: leading_number(number)?,
+    })
+}
+
+/// Parse the number at the start of `s`, e.g., `1234` from `1234-foo`.
+pub fn leading_number(s: &str) -> Option<u64> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s[..end].parse().ok()
+}
+
+impl FromStr for IssueRef {
     type Err = Error;
 
-    fn from_str(s: &str) -> Result<Team> {
+    /// Parse a single reference in any of the forms accepted by `parse_all`, or a bare number.
+    fn from_str(s: &str) -> Result<IssueRef> {
+        if let Ok(number) = s.parse() {
+            return Ok(IssueRef {
+                repo: DEFAULT_ISSUE_REPO.to_owned(),
+                number,
+            });
+        }
+        match &*IssueRef::parse_all(s) {
+            [r] => Ok(r.clone()),
+            _ => Err(Error::ParseArg(s.to_owned())),
+        }
+    }
+}
+
+impl TryFrom<String> for IssueRef {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<IssueRef> {
+        s.parse()
+    }
+}
+
+impl From<IssueRef> for String {
+    fn from(r: IssueRef) -> String {
+        r.to_string()
+    }
+}
+
+impl fmt::Display for IssueRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}#{}", self.repo, self.number)
+    }
+}
+
+/// The state of a tracking issue on GitHub.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct IssueStatus {
+    pub issue: IssueRef,
+    pub open: bool,
+    pub labels: Vec<String>,
+    /// When the issue was last updated.
+    pub updated: NaiveDate,
+}
+
+/// The history of an RFC's text, from the commits to the RFC repo.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct History {
+    /// When the text was first committed, i.e., when the RFC was merged.
+    pub merged: NaiveDate,
+    /// When the text was last changed.
+    pub modified: NaiveDate,
+    /// The authors of the commits which changed the text, in order of their first change.
+    pub authors: Vec<String>,
+    /// Changes to the text after the RFC was merged, oldest first.
+    pub amendments: Vec<Amendment>,
+}
+
+/// A change to the text of an RFC after it was merged.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct Amendment {
+    /// The hash of the commit which made the change (for a PR, the merge commit).
+    pub commit: String,
+    pub date: NaiveDate,
+    /// The PR which made the change, if known.
+    pub pr: Option<u64>,
+    /// The title of the PR, or the summary line of the commit.
+    pub summary: String,
+    /// The author of the PR, `None` if the PR has not been fetched from GitHub.
+    pub author: Option<String>,
+}
+
+/// Labels on a tracking issue which mean that work on it has stopped.
+const STALLED_LABELS: &[&str] = &["S-tracking-design-concerns", "S-tracking-perma-unstable"];
+/// An open tracking issue which has not been updated for this long is considered stalled.
+const STALLED_DAYS: i64 = 365;
+
+/// How far the implementation of an accepted RFC has got.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Progress {
+    /// All tracking issues are closed.
+    Implemented,
+    Ongoing,
+    /// All open tracking issues are blocked or have not been updated for a long time.
+    Stalled,
+}
+
+impl Progress {
+    pub const ALL: [Progress; 3] = [Progress::Implemented, Progress::Ongoing, Progress::Stalled];
+
+    /// A name suitable for use as an identifier, e.g., in CSS classes.
+    pub fn id(self) -> &'static str {
+        match self {
+            Progress::Implemented => "implemented",
+            Progress::Ongoing => "in-progress",
+            Progress::Stalled => "stalled",
+        }
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Progress::Implemented => write!(f, "implemented"),
+            Progress::Ongoing => write!(f, "in progress"),
+            Progress::Stalled => write!(f, "stalled"),
+        }
+    }
+}
+
+/// How far through the RFC process an RFC is.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// An open PR on the RFC repo.
+    Proposed,
+    /// An open PR which is in (or proposed for) its final comment period.
+    Fcp,
+    #[default]
+    Merged,
+    /// The PR was closed as postponed.
+    Postponed,
+    /// The PR was closed without being merged.
+    Closed,
+    Implemented,
+    Stabilized,
+    /// The RFC was accepted but has since been retired or superseded.
+    Retired,
+}
+
+impl Status {
+    pub const ALL: [Status; 8] = [
+        Status::Proposed,
+        Status::Fcp,
+        Status::Merged,
+        Status::Postponed,
+        Status::Closed,
+        Status::Implemented,
+        Status::Stabilized,
+        Status::Retired,
+    ];
+
+    /// True if the RFC has been accepted (i.e., its PR was merged).
+    pub fn is_accepted(self) -> bool {
+        matches!(
+            self,
+            Status::Merged | Status::Implemented | Status::Stabilized | Status::Retired
+        )
+    }
+
+    /// True if the RFC is an open PR.
+    pub fn is_open(self) -> bool {
+        matches!(self, Status::Proposed | Status::Fcp)
+    }
+}
+
+impl FromStr for Status {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Status> {
         match s {
-            "lang" => Ok(Team::Lang),
-            "libs" => Ok(Team::Libs),
-            "core" => Ok(Team::Core),
-            "tools" => Ok(Team::Tools),
-            "compiler" => Ok(Team::Compiler),
-            "docs" => Ok(Team::Docs),
-            _ => Err(Error::ParseTag(s.to_owned())),
+            "proposed" => Ok(Status::Proposed),
+            "fcp" => Ok(Status::Fcp),
+            "merged" => Ok(Status::Merged),
+            "postponed" => Ok(Status::Postponed),
+            "closed" => Ok(Status::Closed),
+            "implemented" => Ok(Status::Implemented),
+            "stabilized" => Ok(Status::Stabilized),
+            "retired" => Ok(Status::Retired),
+            _ => Err(Error::ParseArg(s.to_owned())),
         }
     }
 }
 
-impl fmt::Display for Team {
+impl fmt::Display for Status {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Team::Lang => write!(f, "lang"),
-            Team::Libs => write!(f, "libs"),
-            Team::Core => write!(f, "core"),
-            Team::Tools => write!(f, "tools"),
-            Team::Compiler => write!(f, "compiler"),
-            Team::Docs => write!(f, "docs"),
+            Status::Proposed => write!(f, "proposed"),
+            Status::Fcp => write!(f, "fcp"),
+            Status::Merged => write!(f, "merged"),
+            Status::Postponed => write!(f, "postponed"),
+            Status::Closed => write!(f, "closed"),
+            Status::Implemented => write!(f, "implemented"),
+            Status::Stabilized => write!(f, "stabilized"),
+            Status::Retired => write!(f, "retired"),
+        }
+    }
+}
+
+/// A team, identified by its id in the team metadata (see `TeamInfo`), e.g., `lang`.
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Ord, PartialOrd, Clone, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct Team(String);
+
+impl Team {
+    pub fn id(&self) -> &str {
+        &self.0
+    }
+}
+
+impl TryFrom<String> for Team {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Team> {
+        // Ids are used in the index page's element ids, where `-` separates the team and tag.
+        if s.is_empty() || !s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(Error::ParseTeam(s));
+        }
+        // Older metadata used capitalised names, e.g., `Lang`.
+        Ok(Team(s.to_lowercase()))
+    }
+}
+
+impl From<Team> for String {
+    fn from(team: Team) -> String {
+        team.0
+    }
+}
+
+impl FromStr for Team {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Team> {
+        s.to_owned().try_into()
+    }
+}
+
+impl fmt::Display for Team {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A team as defined in the team metadata.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TeamInfo {
+    pub id: Team,
+    /// The name shown on the website, if different from the id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// GitHub labels on RFC PRs which assign the RFC to the team, e.g., `T-lang`.
+    #[serde(default)]
+    pub labels: Vec<String>,
+}
+
+impl TeamInfo {
+    /// The name to show for the team.
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(self.id.id())
+    }
+}
+
+pub struct TeamMetadata {
+    /// All teams, in the order they are shown on the website.
+    pub teams: Vec<TeamInfo>,
+}
+
+impl TeamMetadata {
+    pub fn get(&self, team: &Team) -> Option<&TeamInfo> {
+        self.teams.iter().find(|t| t.id == *team)
+    }
+
+    /// Parse a user-supplied team, which must be in the team metadata.
+    pub fn parse(&self, s: &str) -> Result<Team> {
+        let team: Team = s.parse()?;
+        match self.get(&team) {
+            Some(_) => Ok(team),
+            None => Err(Error::UnknownTeam(team.0)),
+        }
+    }
+
+    /// The team a PR label assigns an RFC to, if any.
+    pub fn for_label(&self, label: &str) -> Option<&Team> {
+        self.teams
+            .iter()
+            .find(|t| t.labels.iter().any(|l| l == label))
+            .map(|t| &t.id)
+    }
+
+    /// The name to show for `team`, which need not be in the team metadata.
+    pub fn name<'a>(&'a self, team: &'a Team) -> &'a str {
+        match self.get(team) {
+            Some(t) => t.name(),
+            None => team.id(),
         }
     }
 }
 
+/// The tags of a team, the original format of the tag metadata.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TeamTags {
     pub team: Team,
     pub tags: Vec<String>,
 }
 
+/// A tag in the taxonomy of tags.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Tag {
+    /// The tag as used in RFC metadata and PR labels, e.g., `A-traits`.
+    pub tag: String,
+    /// The name shown on the website, if different from the tag without its `A-`/`T-` prefix.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// A broader tag, e.g., `A-typesystem` for `A-traits`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    /// Other names for the tag, e.g., `trait`. Aliases are resolved to the tag by `tag --add`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// If the tag should no longer be used, the tag to use instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<String>,
+    pub teams: Vec<Team>,
+}
+
+impl Tag {
+    pub fn new(tag: String, teams: Vec<Team>) -> Tag {
+        Tag {
+            tag,
+            name: None,
+            description: None,
+            parent: None,
+            aliases: Vec::new(),
+            deprecated: None,
+            teams,
+        }
+    }
+
+    /// The name to show for the tag.
+    pub fn name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => default_tag_name(&self.tag),
+        }
+    }
+}
+
+/// The name of a tag without a display name: the tag without its `A-`/`T-` prefix.
+pub fn default_tag_name(tag: &str) -> String {
+    if tag.starts_with("A-") || tag.starts_with("T-") {
+        tag[2..].to_lowercase()
+    } else {
+        tag.to_lowercase()
+    }
+}
+
+/// The tag metadata file, either the taxonomy or, before it was introduced, tags by team.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TagFile {
+    Taxonomy(Vec<Tag>),
+    ByTeam(Vec<TeamTags>),
+}
+
 pub struct TagMetadata {
+    /// All tags, sorted by tag.
+    pub tags: Vec<Tag>,
     pub by_tag: HashMap<String, Vec<Team>>,
     pub by_team: HashMap<Team, Vec<String>>,
+    /// Lower-cased tags and aliases, to tags.
+    names: HashMap<String, String>,
 }
 
+impl TagMetadata {
+    pub fn new(mut tags: Vec<Tag>) -> TagMetadata {
+        tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+
+        let mut by_tag = HashMap::new();
+        let mut by_team: HashMap<Team, Vec<String>> = HashMap::new();
+        let mut names = HashMap::new();
+        for t in &tags {
+            by_tag.insert(t.tag.clone(), t.teams.clone());
+            for team in &t.teams {
+                by_team.entry(team.clone()).or_default().push(t.tag.clone());
+            }
+            for alias in &t.aliases {
+                names.insert(alias.to_lowercase(), t.tag.clone());
+            }
+        }
+        // Tags take precedence over aliases.
+        for t in &tags {
+            names.insert(t.tag.to_lowercase(), t.tag.clone());
+        }
+
+        TagMetadata {
+            tags,
+            by_tag,
+            by_team,
+            names,
+        }
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&Tag> {
+        self.tags
+            .binary_search_by(|t| (*t.tag).cmp(tag))
+            .ok()
+            .map(|i| &self.tags[i])
+    }
+
+    /// The tag which should be used for `name`, which may be a tag, an alias, or a deprecated tag
+    /// (ignoring case). `None` if `name` is unknown.
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        let mut tag = self.get(self.names.get(&name.to_lowercase())?)?;
+        // Guard against cycles of deprecations.
+        for _ in 0..self.tags.len() {
+            match tag.deprecated.as_deref().and_then(|d| self.get(d)) {
+                Some(t) => tag = t,
+                None => break,
+            }
+        }
+        Some(&tag.tag)
+    }
+
+    /// The tags whose parent is `tag`.
+    pub fn children(&self, tag: &str) -> Vec<&Tag> {
+        self.tags
+            .iter()
+            .filter(|t| t.parent.as_deref() == Some(tag))
+            .collect()
+    }
+
+    /// `tag` and all tags below it in the hierarchy.
+    pub fn descendants<'a>(&'a self, tag: &'a str) -> Vec<&'a str> {
+        let mut result = vec![tag];
+        let mut i = 0;
+        while i < result.len() {
+            for child in self.children(result[i]) {
+                if !result.contains(&&*child.tag) {
+                    result.push(&child.tag);
+                }
+            }
+            i += 1;
+        }
+        result
+    }
+
+    /// The name to show for `tag`, which need not be in the tag metadata.
+    pub fn name(&self, tag: &str) -> String {
+        match self.get(tag) {
+            Some(t) => t.name(),
+            None => default_tag_name(tag),
+        }
+    }
+}
+
+/// Convert tags by team into the taxonomy, or add them to an existing taxonomy.
+pub fn add_team_tags(tags: &mut Vec<Tag>, team_tags: Vec<TeamTags>) {
+    for tt in team_tags {
+        for t in tt.tags {
+            match tags.iter_mut().find(|tag| tag.tag == t) {
+                Some(tag) if !tag.teams.contains(&tt.team) => tag.teams.push(tt.team.clone()),
+                Some(_) => {}
+                None => tags.push(Tag::new(t, vec![tt.team.clone()])),
+            }
+        }
+    }
+}
+
+fn tag_metadata_path() -> PathBuf {
+    config().metadata_dir.join(TAG_METADATA_FILENAME)
+}
+
+pub fn tag_metadata_exists() -> bool {
+    tag_metadata_path().exists()
+}
+
+/// Reads the tag metadata, converting it from tags by team if necessary (see `migrate`).
 pub fn read_tag_metadata() -> Result<TagMetadata> {
-    let mut tags_path = PathBuf::from(METADATA_DIR);
-    tags_path.push(TAG_METADATA_FILENAME);
-    let mut file = File::open(&tags_path)?;
+    let (tags, _) = read_tags()?;
+    Ok(TagMetadata::new(tags))
+}
+
+/// Reads the tags from the tag metadata file, and whether the file is in the current format.
+pub fn read_tags() -> Result<(Vec<Tag>, bool)> {
+    let mut file = File::open(tag_metadata_path())?;
     let mut serialized = String::new();
     file.read_to_string(&mut serialized)?;
-    let tags: Vec<TeamTags> = serde_json::from_str(&serialized)?;
+    Ok(match serde_json::from_str(&serialized)? {
+        TagFile::Taxonomy(tags) => (tags, true),
+        TagFile::ByTeam(team_tags) => {
+            let mut tags = Vec::new();
+            add_team_tags(&mut tags, team_tags);
+            (tags, false)
+        }
+    })
+}
+
+pub fn write_tag_metadata(tags: &[Tag]) -> Result<()> {
+    let serialized = serialize_tags(tags)?;
+    if preview(&tag_metadata_path(), Some(&serialized))? {
+        return Ok(());
+    }
+    write_atomic(&tag_metadata_path(), &serialized)
+}
 
-    let mut by_tag = HashMap::new();
-    let mut by_team = HashMap::new();
+fn serialize_tags(tags: &[Tag]) -> Result<String> {
+    let mut tags = tags.to_vec();
+    tags.sort_by(|a, b| a.tag.cmp(&b.tag));
+    // The taxonomy is edited by hand, so keep it readable.
+    Ok(serde_json::to_string_pretty(&tags)?)
+}
 
-    for tt in tags {
-        for t in &tt.tags {
-            by_tag
-                .entry(t.clone())
-                .or_insert_with(|| Vec::new())
-                .push(tt.team);
-        }
-        by_team.insert(tt.team, tt.tags);
+pub fn read_team_metadata() -> Result<TeamMetadata> {
+    let mut file = File::open(config().metadata_dir.join(TEAM_METADATA_FILENAME))?;
+    let mut serialized = String::new();
+    file.read_to_string(&mut serialized)?;
+    Ok(TeamMetadata {
+        teams: serde_json::from_str(&serialized)?,
+    })
+}
+
+fn metadata_filename(number: u64) -> PathBuf {
+    config().metadata_dir.join(format!("{:0>4}.json", number))
+}
+
+pub fn save_metadata(metadata: &RfcMetadata) -> Result<()> {
+    let path = metadata_filename(metadata.number);
+    let serialized = serde_json::to_string(metadata)?;
+    if preview(&path, Some(&serialized))? {
+        return Ok(());
     }
+    write_atomic(&path, &serialized)
+}
 
-    Ok(TagMetadata { by_tag, by_team })
+/// With `--dry-run`, print the change to a metadata file (`None` to delete it) instead of making
+/// it. Returns whether the change was previewed.
+fn preview(path: &Path, contents: Option<&str>) -> Result<bool> {
+    let format = match config().dry_run {
+        Some(format) => format,
+        None => return Ok(false),
+    };
+    let before = match fs::read_to_string(path) {
+        Ok(s) => Some(s),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    for change in file_changes(before.as_deref(), contents)? {
+        println!("{}", change.format(format)?);
+    }
+    Ok(true)
 }
 
-pub fn write_tag_metadata(tags: Vec<TeamTags>) -> Result<()> {
-    let serialized = serde_json::to_string(&tags)?;
-    let mut tags_path = PathBuf::from(METADATA_DIR);
-    tags_path.push(TAG_METADATA_FILENAME);
+/// The path of a temporary file next to `path`, e.g., `metadata/.0001.json.tmp`. Temporary files
+/// are hidden, and are not read as metadata.
+fn temp_path(path: &Path, extension: &str) -> PathBuf {
+    let name = path.file_name().unwrap().to_string_lossy();
+    path.with_file_name(format!(".{}.{}", name, extension))
+}
 
-    let mut file = File::create(&tags_path)?;
-    file.write_all(serialized.as_bytes())?;
+fn write_synced(path: &Path, contents: &str) -> Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
     Ok(())
 }
 
-fn metadata_filename(number: u64) -> String {
-    format!("{}/{:0>4}.json", METADATA_DIR, number)
+/// Write a file by writing a temporary file and renaming it, so that an interrupted write never
+/// leaves a truncated file.
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let temp = temp_path(path, "tmp");
+    let result = write_synced(&temp, contents).and_then(|_| Ok(fs::rename(&temp, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
 }
 
-pub fn save_metadata(metadata: &RfcMetadata) -> Result<()> {
-    let serialized = serde_json::to_string(metadata)?;
-    let mut file = File::create(metadata_filename(metadata.number))?;
-    file.write_all(serialized.as_bytes())?;
+/// Changes to metadata files which are made together, so that a bulk edit is either applied to all
+/// files or (if there is an error) to none.
+///
+/// Changes are staged in memory. `commit` writes all the new files to temporary files before
+/// replacing any metadata, so most errors (e.g., a full disk) leave the metadata unchanged. The
+/// old files are then moved aside (to `.*.bak` files) while the new files are moved into place,
+/// and restored if that fails. A journal records the files being replaced, so that if the process
+/// is killed part way through, `recover` can finish (or finish rolling back) the changes.
+#[derive(Default)]
+pub struct Transaction {
+    /// The new contents of each changed file, or `None` if the file is deleted.
+    changes: Vec<(PathBuf, Option<String>)>,
+}
+
+const JOURNAL_FILENAME: &str = ".transaction.json";
+
+/// The state of a `Transaction` while its files are being replaced.
+#[derive(Serialize, Deserialize, Debug)]
+struct Journal {
+    /// Whether the changes are being rolled back, rather than completed.
+    rollback: bool,
+    files: Vec<JournalEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct JournalEntry {
+    /// Relative to the journal's directory (i.e., the metadata directory), so that the journal
+    /// does not depend on the current directory.
+    path: PathBuf,
+    /// Whether the file existed before the transaction.
+    existed: bool,
+    /// Whether the file is replaced, rather than deleted.
+    replaced: bool,
+}
+
+impl Transaction {
+    pub fn new() -> Transaction {
+        Transaction::default()
+    }
+
+    pub fn save_metadata(&mut self, metadata: &RfcMetadata) -> Result<()> {
+        let serialized = serde_json::to_string(metadata)?;
+        self.stage(metadata_filename(metadata.number), Some(serialized));
+        Ok(())
+    }
+
+    pub fn write_tag_metadata(&mut self, tags: &[Tag]) -> Result<()> {
+        self.stage(tag_metadata_path(), Some(serialize_tags(tags)?));
+        Ok(())
+    }
+
+    /// Stage a change, replacing any earlier change to the same file.
+    fn stage(&mut self, path: PathBuf, contents: Option<String>) {
+        match self.changes.iter_mut().find(|(p, _)| *p == path) {
+            Some(change) => change.1 = contents,
+            None => self.changes.push((path, contents)),
+        }
+    }
+
+    pub fn commit(self) -> Result<()> {
+        if config().dry_run.is_some() {
+            for (path, contents) in &self.changes {
+                preview(path, contents.as_deref())?;
+            }
+            return Ok(());
+        }
+        let journal_path = match self.changes.first() {
+            Some((path, _)) => path.with_file_name(JOURNAL_FILENAME),
+            None => return Ok(()),
+        };
+        let dir = journal_path.parent().unwrap();
+
+        for (path, contents) in &self.changes {
+            if let Some(contents) = contents {
+                if let Err(e) = write_synced(&temp_path(path, "tmp"), contents) {
+                    self.clean_up();
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut journal = Journal {
+            rollback: false,
+            files: self
+                .changes
+                .iter()
+                .map(|(path, contents)| JournalEntry {
+                    path: path.strip_prefix(dir).unwrap_or(path).to_owned(),
+                    existed: path.exists(),
+                    replaced: contents.is_some(),
+                })
+                .collect(),
+        };
+        if let Err(e) = write_atomic(&journal_path, &serde_json::to_string(&journal)?) {
+            self.clean_up();
+            return Err(e);
+        }
+
+        for (path, contents) in &self.changes {
+            let result = (|| -> Result<()> {
+                if path.exists() {
+                    fs::rename(path, temp_path(path, "bak"))?;
+                }
+                if contents.is_some() {
+                    fs::rename(temp_path(path, "tmp"), path)?;
+                }
+                Ok(())
+            })();
+            if let Err(e) = result {
+                journal.rollback = true;
+                let _ = write_atomic(&journal_path, &serde_json::to_string(&journal)?);
+                journal.rollback_files(dir);
+                let _ = fs::remove_file(&journal_path);
+                return Err(e);
+            }
+        }
+
+        fs::remove_file(&journal_path)?;
+        self.clean_up();
+        Ok(())
+    }
+
+    /// Remove temporary files and backups.
+    fn clean_up(&self) {
+        for (path, _) in &self.changes {
+            let _ = fs::remove_file(temp_path(path, "tmp"));
+            let _ = fs::remove_file(temp_path(path, "bak"));
+        }
+    }
+}
+
+impl Journal {
+    /// Move the new files into place, and remove the old ones.
+    fn complete_files(&self, dir: &Path) {
+        for f in &self.files {
+            let path = dir.join(&f.path);
+            let temp = temp_path(&path, "tmp");
+            if temp.exists() {
+                let _ = fs::rename(&temp, &path);
+            } else if !f.replaced {
+                let _ = fs::remove_file(&path);
+            }
+            let _ = fs::remove_file(temp_path(&path, "bak"));
+        }
+    }
+
+    /// Restore the old files, and remove the new ones.
+    fn rollback_files(&self, dir: &Path) {
+        for f in &self.files {
+            let path = dir.join(&f.path);
+            let backup = temp_path(&path, "bak");
+            if backup.exists() {
+                let _ = fs::rename(&backup, &path);
+            } else if !f.existed {
+                let _ = fs::remove_file(&path);
+            }
+            let _ = fs::remove_file(temp_path(&path, "tmp"));
+        }
+    }
+}
+
+/// Finish a `Transaction` in `dir` which was interrupted, e.g., by the process being killed, and
+/// remove any leftover temporary files and backups.
+pub fn recover(dir: &Path) -> Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+    let journal_path = dir.join(JOURNAL_FILENAME);
+    match fs::read_to_string(&journal_path) {
+        Ok(s) => {
+            let journal: Journal = serde_json::from_str(&s)?;
+            eprintln!(
+                "warning: {} an interrupted edit of {} files",
+                if journal.rollback {
+                    "rolling back"
+                } else {
+                    "completing"
+                },
+                journal.files.len()
+            );
+            if journal.rollback {
+                journal.rollback_files(dir);
+            } else {
+                journal.complete_files(dir);
+            }
+            fs::remove_file(&journal_path)?;
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    // Without a journal, no metadata has been replaced, so these are only left over.
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') && (name.ends_with(".tmp") || name.ends_with(".bak")) {
+            let _ = fs::remove_file(dir.join(name));
+        }
+    }
     Ok(())
 }
 
 pub fn open_metadata(number: u64) -> Result<RfcMetadata> {
-    read_metadata(Path::new(&metadata_filename(number)))
+    read_metadata(&metadata_filename(number))
+}
+
+/// Reads metadata from disk, upgrading it to the current version if necessary. Does not save
+/// upgraded metadata to disk (see `migrate`).
+pub fn read_metadata(path: &Path) -> Result<RfcMetadata> {
+    let mut value = read_raw_metadata(path)?;
+    upgrade(&mut value)?;
+    Ok(serde_json::from_value(value)?)
 }
 
-fn read_metadata(path: &Path) -> Result<RfcMetadata> {
+/// Reads metadata from disk as JSON, without checking its version or format.
+pub fn read_raw_metadata(path: &Path) -> Result<serde_json::Value> {
     let mut file = File::open(path)?;
     let mut serialized = String::new();
     file.read_to_string(&mut serialized)?;
-    let result: RfcMetadata = serde_json::from_str(&serialized)?;
-
-    if result.version > METADATA_VERSION {
-        Err(Error::UnsupportedMetadataVersion(result.version))
-    } else {
-        Ok(result)
-    }
+    Ok(serde_json::from_str(&serialized)?)
 }
 
 pub fn delete_metadata(number: u64) -> Result<()> {
+    if preview(&metadata_filename(number), None)? {
+        return Ok(());
+    }
     fs::remove_file(metadata_filename(number))?;
     Ok(())
 }
 
 pub fn metadata_exists(number: u64) -> Result<()> {
-    Path::new(&metadata_filename(number))
+    metadata_filename(number)
         .exists()
         .then(|| ())
         .ok_or(Error::FileNotFound)
 }
 
 pub fn all_metadata() -> Result<Vec<RfcMetadata>> {
-    fs::read_dir(METADATA_DIR)?
+    all_metadata_paths()?
+        .iter()
+        .map(|p| read_metadata(p))
+        .collect()
+}
+
+/// Paths of all RFC metadata files (i.e., excluding tag metadata).
+pub fn all_metadata_paths() -> Result<Vec<PathBuf>> {
+    Ok(fs::read_dir(&config().metadata_dir)?
         .filter_map(|e| e.ok())
         .filter(|e| {
             !e.file_type().unwrap().is_dir()
@@ -201,12 +1038,12 @@ pub fn all_metadata() -> Result<Vec<RfcMetadata>> {
                     .parse::<u64>()
                     .is_ok()
         })
-        .map(|e| read_metadata(&e.path()))
-        .collect()
+        .map(|e| e.path())
+        .collect())
 }
 
 pub fn all_metadata_numbers() -> Result<Vec<u64>> {
-    Ok(fs::read_dir(METADATA_DIR)?
+    Ok(fs::read_dir(&config().metadata_dir)?
         .filter_map(|e| e.ok())
         .filter(|e| !e.file_type().unwrap().is_dir())
         .filter_map(|e| {
@@ -230,8 +1067,19 @@ pub fn infer_team_from_tags(metadata: &mut RfcMetadata, tag_metadata: &TagMetada
 
     let mut teams = HashMap::new();
     for t in &metadata.tags {
-        for team in &tag_metadata.by_tag[t] {
-            *teams.entry(*team).or_insert_with(|| 0) += 1;
+        // Unknown tags are reported by `validate`.
+        let tag_teams = match tag_metadata.by_tag.get(t) {
+            Some(teams) => teams,
+            None => {
+                eprintln!(
+                    "warning: RFC {}: tag `{}` is not in the tag metadata",
+                    metadata.number, t
+                );
+                continue;
+            }
+        };
+        for team in tag_teams {
+            *teams.entry(team.clone()).or_insert_with(|| 0) += 1;
         }
     }
 
@@ -254,3 +1102,269 @@ pub fn infer_team_from_tags(metadata: &mut RfcMetadata, tag_metadata: &TagMetada
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn infer_team_from_tags_() {
+        let lang: Team = "lang".parse().unwrap();
+        let tag_metadata = TagMetadata::new(vec![
+            Tag::new("A-traits".to_owned(), vec![lang.clone()]),
+            Tag::new("A-dyn".to_owned(), vec![lang.clone()]),
+        ]);
+        let mut metadata = RfcMetadata::new(1, String::new(), None);
+        metadata.tags = vec![
+            "A-traits".to_owned(),
+            "A-stale".to_owned(),
+            "A-dyn".to_owned(),
+        ];
+        infer_team_from_tags(&mut metadata, &tag_metadata).unwrap();
+        assert_eq!(metadata.teams, vec![lang]);
+    }
+
+    #[test]
+    fn parse_date_() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+        assert_eq!(parse_date("2014-07-17"), date(2014, 7, 17));
+        assert_eq!(parse_date(" 2015-1-30 "), date(2015, 1, 30));
+        assert_eq!(parse_date("2015/01/30"), date(2015, 1, 30));
+        assert_eq!(parse_date("23-07-2014"), date(2014, 7, 23));
+        assert_eq!(parse_date("23/07/2014"), date(2014, 7, 23));
+        assert_eq!(parse_date("01 March, 2016"), date(2016, 3, 1));
+        assert_eq!(parse_date("1 March 2016"), date(2016, 3, 1));
+        assert_eq!(parse_date("March 1, 2016"), date(2016, 3, 1));
+        assert_eq!(
+            parse_date("(fill me in with today's date, 2014-07-17)"),
+            date(2014, 7, 17)
+        );
+
+        // These become `null` when migrating the start date.
+        assert_eq!(parse_date(""), None);
+        assert_eq!(
+            parse_date("(fill me in with today's date, YYYY-MM-DD)"),
+            None
+        );
+        assert_eq!(parse_date("2014-13-01"), None);
+        assert_eq!(parse_date("2015-02-29"), None);
+        assert_eq!(parse_date("July 2014"), None);
+        assert_eq!(parse_date("yesterday"), None);
+    }
+
+    #[test]
+    fn issue_ref_parse_all() {
+        let rust = |number| IssueRef {
+            repo: "rust-lang/rust".to_owned(),
+            number,
+        };
+        assert_eq!(
+            IssueRef::parse_all(
+                "[rust-lang/rust#8122](https://github.com/rust-lang/rust/issues/8122)"
+            ),
+            vec![rust(8122)]
+        );
+        assert_eq!(
+            IssueRef::parse_all(
+                "#123, rust-lang/rust#456 and https://github.com/rust-lang/rust/pull/789"
+            ),
+            vec![rust(123), rust(456), rust(789)]
+        );
+        assert_eq!(
+            IssueRef::parse_all("[rust-lang/cargo#1](https://github.com/rust-lang/cargo/issues/2)"),
+            vec![IssueRef {
+                repo: "rust-lang/cargo".to_owned(),
+                number: 2
+            }]
+        );
+        assert!(IssueRef::parse_all("N/A").is_empty());
+        assert!(IssueRef::parse_all("(leave this empty)").is_empty());
+
+        assert_eq!(
+            "1234".parse::<IssueRef>().unwrap().to_string(),
+            "rust-lang/rust#1234"
+        );
+        assert!("foo".parse::<IssueRef>().is_err());
+    }
+
+    #[test]
+    fn progress_() {
+        let date = |s| parse_date(s).unwrap();
+        let status = |number, open, labels: &[&str], updated| IssueStatus {
+            issue: IssueRef {
+                repo: "rust-lang/rust".to_owned(),
+                number,
+            },
+            open,
+            labels: labels.iter().map(|l| l.to_string()).collect(),
+            updated: date(updated),
+        };
+        let today = date("2022-06-01");
+
+        let mut rfc = RfcMetadata::new(1, "0001-private-fields.md".to_owned(), None);
+        assert_eq!(rfc.progress(today), None);
+        rfc.issue_status = vec![status(1, false, &[], "2015-01-01")];
+        assert_eq!(rfc.progress(today), Some(Progress::Implemented));
+        rfc.issue_status
+            .push(status(2, true, &["B-unstable"], "2022-01-01"));
+        assert_eq!(rfc.progress(today), Some(Progress::Ongoing));
+        rfc.issue_status[1].updated = date("2020-01-01");
+        assert_eq!(rfc.progress(today), Some(Progress::Stalled));
+        rfc.status = Status::Proposed;
+        assert_eq!(rfc.progress(today), None);
+    }
+
+    #[test]
+    fn tag_metadata_() {
+        let tags = match serde_json::from_str(
+            r#"[{"team":"Lang","tags":["A-traits","A-dyn"]},{"team":"Libs","tags":["A-traits"]}]"#,
+        )
+        .unwrap()
+        {
+            TagFile::ByTeam(team_tags) => {
+                let mut tags = Vec::new();
+                add_team_tags(&mut tags, team_tags);
+                tags
+            }
+            TagFile::Taxonomy(_) => panic!("expected tags by team"),
+        };
+        let team = |s: &str| s.parse::<Team>().unwrap();
+        let mut tags = TagMetadata::new(tags).tags;
+        assert_eq!(tags[1].tag, "A-traits");
+        assert_eq!(tags[1].teams, vec![team("lang"), team("libs")]);
+
+        tags[0].deprecated = Some("A-trait-object".to_owned());
+        let mut object = Tag::new("A-trait-object".to_owned(), vec![team("lang")]);
+        object.name = Some("trait objects".to_owned());
+        object.parent = Some("A-traits".to_owned());
+        object.aliases = vec!["dyn-trait".to_owned()];
+        tags.push(object);
+        let tags = TagMetadata::new(tags);
+
+        assert_eq!(tags.resolve("a-TRAITS"), Some("A-traits"));
+        assert_eq!(tags.resolve("dyn-trait"), Some("A-trait-object"));
+        assert_eq!(tags.resolve("A-dyn"), Some("A-trait-object"));
+        assert_eq!(tags.resolve("foo"), None);
+        assert_eq!(
+            tags.descendants("A-traits"),
+            vec!["A-traits", "A-trait-object"]
+        );
+        assert_eq!(tags.name("A-trait-object"), "trait objects");
+        assert_eq!(tags.name("A-traits"), "traits");
+    }
+
+    #[test]
+    fn team_metadata_() {
+        let teams = TeamMetadata {
+            teams: serde_json::from_str(
+                r#"[{"id":"lang","name":"Language","labels":["T-lang"]},{"id":"libs","labels":["T-libs","T-libs-api"]}]"#,
+            )
+            .unwrap(),
+        };
+        let libs: Team = "Libs".parse().unwrap();
+        assert_eq!(libs.id(), "libs");
+        assert!("dev-tools".parse::<Team>().is_err());
+
+        assert_eq!(teams.for_label("T-libs-api"), Some(&libs));
+        assert_eq!(teams.for_label("A-traits"), None);
+        assert_eq!(teams.name(&libs), "libs");
+        assert_eq!(teams.name(&teams.parse("lang").unwrap()), "Language");
+        assert!(teams.parse("cargo").is_err());
+    }
+
+    #[test]
+    fn transaction_() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let old = dir.join("0001.json");
+        let new = dir.join("0002.json");
+        fs::write(&old, "old").unwrap();
+
+        let mut transaction = Transaction::new();
+        transaction.stage(old.clone(), Some("one".to_owned()));
+        transaction.stage(new.clone(), Some("two".to_owned()));
+        transaction.stage(old.clone(), Some("1".to_owned()));
+        transaction.commit().unwrap();
+        assert_eq!(fs::read_to_string(&old).unwrap(), "1");
+        assert_eq!(fs::read_to_string(&new).unwrap(), "two");
+
+        // Nothing is changed if any file can't be written.
+        let mut transaction = Transaction::new();
+        transaction.stage(old.clone(), Some("changed".to_owned()));
+        transaction.stage(new.clone(), None);
+        transaction.stage(dir.join("missing/0003.json"), Some("three".to_owned()));
+        assert!(transaction.commit().is_err());
+        assert_eq!(fs::read_to_string(&old).unwrap(), "1");
+        assert!(new.exists());
+
+        // Files which were already replaced are restored if a later file can't be replaced (here,
+        // because its backup can't be written).
+        fs::create_dir_all(dir.join(".0002.json.bak/blocked")).unwrap();
+        let mut transaction = Transaction::new();
+        transaction.stage(old.clone(), Some("changed".to_owned()));
+        transaction.stage(new.clone(), Some("changed".to_owned()));
+        assert!(transaction.commit().is_err());
+        assert_eq!(fs::read_to_string(&old).unwrap(), "1");
+        assert_eq!(fs::read_to_string(&new).unwrap(), "two");
+        fs::remove_dir_all(dir.join(".0002.json.bak")).unwrap();
+
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["0001.json", "0002.json"]);
+    }
+
+    #[test]
+    fn recover_() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let read = |name: &str| fs::read_to_string(dir.join(name)).ok();
+        // A transaction replacing 0001 and 0002 and adding 0003, killed after replacing 0001.
+        let interrupted = |rollback| {
+            fs::write(dir.join("0001.json"), "new 1").unwrap();
+            fs::write(dir.join(".0001.json.bak"), "old 1").unwrap();
+            fs::write(dir.join("0002.json"), "old 2").unwrap();
+            fs::write(dir.join(".0002.json.tmp"), "new 2").unwrap();
+            fs::write(dir.join(".0003.json.tmp"), "new 3").unwrap();
+            let journal = Journal {
+                rollback,
+                files: (1..=3)
+                    .map(|n| JournalEntry {
+                        path: format!("{:0>4}.json", n).into(),
+                        existed: n < 3,
+                        replaced: true,
+                    })
+                    .collect(),
+            };
+            let journal = serde_json::to_string(&journal).unwrap();
+            fs::write(dir.join(JOURNAL_FILENAME), journal).unwrap();
+        };
+
+        interrupted(false);
+        recover(dir).unwrap();
+        assert_eq!(read("0001.json").as_deref(), Some("new 1"));
+        assert_eq!(read("0002.json").as_deref(), Some("new 2"));
+        assert_eq!(read("0003.json").as_deref(), Some("new 3"));
+
+        fs::remove_file(dir.join("0003.json")).unwrap();
+        interrupted(true);
+        recover(dir).unwrap();
+        assert_eq!(read("0001.json").as_deref(), Some("old 1"));
+        assert_eq!(read("0002.json").as_deref(), Some("old 2"));
+        assert_eq!(read("0003.json"), None);
+
+        // Without a journal, no metadata was replaced.
+        fs::write(dir.join(".0002.json.tmp"), "new 2").unwrap();
+        recover(dir).unwrap();
+        assert_eq!(read("0002.json").as_deref(), Some("old 2"));
+
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["0001.json", "0002.json"]);
+    }
+}
@@ -0,0 +1,227 @@
+//! Migration of metadata between versions of the metadata format.
+//!
+//! Migrations operate on the raw JSON so that old metadata can be read even if it can no longer be
+//! deserialized into `RfcMetadata`. To change the format, bump `METADATA_VERSION` and add a step
+//! to `MIGRATIONS`.
+
+use crate::{
+    config::config,
+    errors::{Error, Result},
+    metadata::{
+        all_metadata_paths, parse_date, read_raw_metadata, read_tags, tag_metadata_exists,
+        IssueRef, RfcMetadata, Transaction, METADATA_VERSION,
+    },
+};
+use serde_json::{Map, Value};
+
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// `MIGRATIONS[n]` upgrades metadata from version `n + 1` to version `n + 2`.
+const MIGRATIONS: [Migration; METADATA_VERSION as usize - 1] = [
+    v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6, v6_to_v7, v7_to_v8, v8_to_v9, v9_to_v10,
+];
+
+/// `status` was added, all RFCs indexed before then were merged.
+fn v1_to_v2(metadata: &mut Map<String, Value>) -> Result<()> {
+    metadata.insert("status".to_owned(), Value::String("Merged".to_owned()));
+    Ok(())
+}
+
+/// `start_date` changed from free text to an optional `YYYY-MM-DD` date.
+fn v2_to_v3(metadata: &mut Map<String, Value>) -> Result<()> {
+    let text = metadata
+        .get("start_date")
+        .and_then(|d| d.as_str())
+        .ok_or(Error::Parse)?;
+    let date = match parse_date(text) {
+        Some(d) => Value::String(d.to_string()),
+        None => {
+            eprintln!(
+                "warning: RFC {}: could not parse start date `{}`",
+                metadata["number"], text
+            );
+            Value::Null
+        }
+    };
+    metadata.insert("start_date".to_owned(), date);
+    Ok(())
+}
+
+/// `issues` changed from fragments of the header text to canonical `owner/repo#1234` references.
+fn v3_to_v4(metadata: &mut Map<String, Value>) -> Result<()> {
+    let parts: Vec<&str> = metadata
+        .get("issues")
+        .and_then(|i| i.as_array())
+        .ok_or(Error::Parse)?
+        .iter()
+        .filter_map(|i| i.as_str())
+        .collect();
+    // The old parser split on `/`, so rejoining on `/` restores any URLs.
+    let text = parts.join("/");
+    let issues = IssueRef::parse_all(&text);
+    if issues.is_empty() && !text.is_empty() {
+        eprintln!(
+            "warning: RFC {}: no issue references found in `{}`",
+            metadata["number"], text
+        );
+    }
+    let issues = issues
+        .into_iter()
+        .map(|i| Value::String(i.to_string()))
+        .collect();
+    metadata.insert("issues".to_owned(), Value::Array(issues));
+    Ok(())
+}
+
+/// `issue_status` was added, it is filled in by `scan --issues`.
+fn v4_to_v5(metadata: &mut Map<String, Value>) -> Result<()> {
+    metadata.insert("issue_status".to_owned(), Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// `history` was added, it is filled in by `scan --history`.
+fn v5_to_v6(metadata: &mut Map<String, Value>) -> Result<()> {
+    metadata.insert("history".to_owned(), Value::Null);
+    Ok(())
+}
+
+/// `author` was added to amendments, it is filled in from the amending PR by `scan --history`.
+fn v6_to_v7(metadata: &mut Map<String, Value>) -> Result<()> {
+    let amendments = metadata
+        .get_mut("history")
+        .and_then(|h| h.get_mut("amendments"))
+        .and_then(|a| a.as_array_mut());
+    for amendment in amendments.into_iter().flatten() {
+        let amendment = amendment.as_object_mut().ok_or(Error::Parse)?;
+        amendment.insert("author".to_owned(), Value::Null);
+    }
+    Ok(())
+}
+
+/// `references` and `referenced_by` were added, they are filled in by `scan --references`.
+fn v7_to_v8(metadata: &mut Map<String, Value>) -> Result<()> {
+    metadata.insert("references".to_owned(), Value::Array(Vec::new()));
+    metadata.insert("referenced_by".to_owned(), Value::Array(Vec::new()));
+    Ok(())
+}
+
+/// Teams changed from a fixed set of capitalised names (`Lang`) to the ids in the team metadata
+/// (`lang`).
+fn v8_to_v9(metadata: &mut Map<String, Value>) -> Result<()> {
+    let teams = metadata
+        .get_mut("teams")
+        .and_then(|t| t.as_array_mut())
+        .ok_or(Error::Parse)?;
+    for t in teams {
+        let id = t.as_str().ok_or(Error::Parse)?.to_lowercase();
+        *t = Value::String(id);
+    }
+    Ok(())
+}
+
+/// `status` changed from the variant name, e.g., `Merged`, to lowercase, as it is written
+/// everywhere else.
+fn v9_to_v10(metadata: &mut Map<String, Value>) -> Result<()> {
+    let status = metadata
+        .get("status")
+        .and_then(|s| s.as_str())
+        .ok_or(Error::Parse)?
+        .to_lowercase();
+    metadata.insert("status".to_owned(), Value::String(status));
+    Ok(())
+}
+
+fn version(value: &Value) -> Result<u64> {
+    value
+        .get("version")
+        .and_then(|v| v.as_u64())
+        .ok_or(Error::Parse)
+}
+
+/// Upgrade raw metadata to the current version. Returns the version the metadata was upgraded
+/// from.
+pub fn upgrade(value: &mut Value) -> Result<u64> {
+    let from = version(value)?;
+    if from == 0 || from > METADATA_VERSION {
+        return Err(Error::UnsupportedMetadataVersion(from));
+    }
+
+    let object = value.as_object_mut().ok_or(Error::Parse)?;
+    for v in from..METADATA_VERSION {
+        MIGRATIONS[v as usize - 1](object)?;
+        object.insert("version".to_owned(), Value::from(v + 1));
+    }
+
+    Ok(from)
+}
+
+/// Rewrite all metadata at the current version. With `--dry-run`, report what would change but
+/// don't write anything.
+pub fn migrate_all() -> Result<()> {
+    let mut count = 0;
+    let mut transaction = Transaction::new();
+    for path in all_metadata_paths()? {
+        let mut value = read_raw_metadata(&path)?;
+        if upgrade(&mut value)? == METADATA_VERSION {
+            continue;
+        }
+
+        count += 1;
+        let metadata: RfcMetadata = serde_json::from_value(value)?;
+        transaction.save_metadata(&metadata)?;
+    }
+
+    // The tag metadata used to be a list of tags for each team.
+    let mut migrate_tags = false;
+    if tag_metadata_exists() {
+        let (tags, current) = read_tags()?;
+        if !current {
+            transaction.write_tag_metadata(&tags)?;
+            migrate_tags = true;
+        }
+    }
+
+    transaction.commit()?;
+    let migrated = match config().dry_run {
+        Some(_) => "would be migrated",
+        None => "migrated",
+    };
+    println!(
+        "{} files {} to version {}",
+        count, migrated, METADATA_VERSION
+    );
+    if migrate_tags {
+        println!("tag metadata {} to the tag taxonomy", migrated);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diff::diff_fields;
+
+    #[test]
+    fn upgrade_() {
+        let mut value: Value = serde_json::from_str(
+            r#"{"version":1,"number":1,"filename":"0001-private-fields.md","start_date":"2014-03-11","feature_name":[],"issues":[],"title":null,"teams":["Lang"],"tags":[]}"#,
+        )
+        .unwrap();
+        let before = value.clone();
+        assert_eq!(upgrade(&mut value).unwrap(), 1);
+        assert_eq!(version(&value).unwrap(), METADATA_VERSION);
+        let changes: Vec<_> = diff_fields(&before, &value)
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(
+            changes.join(", "),
+            r#"+history (null), +issue_status ([]), +referenced_by ([]), +references ([]), +status ("merged"), ~teams (["Lang"] -> ["lang"])"#
+        );
+        let metadata: RfcMetadata = serde_json::from_value(value).unwrap();
+        assert_eq!(metadata.status, crate::metadata::Status::Merged);
+
+        let mut value: Value = serde_json::from_str(r#"{"version":1000}"#).unwrap();
+        assert!(upgrade(&mut value).is_err());
+    }
+}
@@ -0,0 +1,330 @@
+//! Printing metadata in machine- and human-readable formats.
+
+use crate::{
+    errors::{Error, Result},
+    metadata::RfcMetadata,
+};
+use serde::{
+    de::{MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_json::Value;
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Format {
+    /// Values separated by whitespace.
+    Plain,
+    Json,
+    /// One JSON object per line.
+    JsonLines,
+    /// Comma-separated values, with a header row.
+    Csv,
+    /// A user-supplied template, fields are substituted for `{field_name}`.
+    Template(String),
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format> {
+        match s {
+            "plain" => Ok(Format::Plain),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            _ if s.contains('{') => {
+                template_fields(s)?;
+                Ok(Format::Template(s.to_owned()))
+            }
+            _ => Err(Error::ParseArg(s.to_owned())),
+        }
+    }
+}
+
+/// The fields of an RFC's metadata, in order. Unlike `serde_json::Map`, which sorts its keys, this
+/// keeps fields in the order they were selected (or declared in `RfcMetadata`).
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Record(Vec<(String, Value)>);
+
+impl Record {
+    fn get(&self, field: &str) -> &Value {
+        &self.0.iter().find(|(f, _)| f == field).unwrap().1
+    }
+
+    fn remove(&mut self, field: &str) -> Option<Value> {
+        let i = self.0.iter().position(|(f, _)| f == field)?;
+        Some(self.0.remove(i).1)
+    }
+}
+
+impl Serialize for Record {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (k, v) in &self.0 {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Record {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Record, D::Error> {
+        struct RecordVisitor;
+
+        impl<'de> Visitor<'de> for RecordVisitor {
+            type Value = Record;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Record, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    fields.push(entry);
+                }
+                Ok(Record(fields))
+            }
+        }
+
+        deserializer.deserialize_map(RecordVisitor)
+    }
+}
+
+/// All fields which can be printed.
+pub fn all_fields() -> Result<Vec<String>> {
+    let prototype = RfcMetadata::new(0, String::new(), None);
+    Ok(to_record(&prototype)?
+        .0
+        .into_iter()
+        .map(|(k, _)| k)
+        .filter(|k| k != "version")
+        .collect())
+}
+
+/// Print `fields` of each RFC's metadata.
+pub fn print_all(metadata: &[RfcMetadata], format: &Format, fields: &[String]) -> Result<()> {
+    let fields = &match format {
+        Format::Template(t) => template_fields(t)?,
+        _ => fields.to_vec(),
+    };
+    let records = metadata
+        .iter()
+        .map(|m| select(m, fields))
+        .collect::<Result<Vec<_>>>()?;
+
+    match format {
+        Format::Plain if fields.len() == 1 => {
+            let values: Vec<_> = records.iter().map(|r| render(r.get(&fields[0]))).collect();
+            println!("{}", values.join(" "));
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&records)?),
+        _ => {
+            if *format == Format::Csv {
+                let header: Vec<_> = fields.iter().map(|f| csv_escape(f)).collect();
+                println!("{}", header.join(","));
+            }
+            for r in &records {
+                println!("{}", format_record(r, format, fields)?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Print `fields` of a single RFC's metadata.
+pub fn print_one(metadata: &RfcMetadata, format: &Format, fields: &[String]) -> Result<()> {
+    match format {
+        Format::Json => {
+            let record = select(metadata, fields)?;
+            println!("{}", serde_json::to_string_pretty(&record)?);
+            Ok(())
+        }
+        _ => print_all(std::slice::from_ref(metadata), format, fields),
+    }
+}
+
+/// Print `fields` of a single RFC's metadata as plain text, one per line, prefixed by the field's
+/// name if `verbose`.
+pub fn print_fields(metadata: &RfcMetadata, fields: &[String], verbose: bool) -> Result<()> {
+    for (field, value) in select(metadata, fields)?.0 {
+        if verbose {
+            println!("{}: {}", field, render(&value));
+        } else {
+            println!("{}", render(&value));
+        }
+    }
+    Ok(())
+}
+
+/// Check that `fields` are not given with a template, which names its own fields.
+pub fn check_fields(format: &Format, fields: &[String]) -> Result<()> {
+    match format {
+        Format::Template(t) if !fields.is_empty() => Err(Error::ParseArg(format!(
+            "fields can't be given with a template: `{}`",
+            t
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Format `fields` of a record as a line of output (for formats other than `Json`, a line per
+/// record).
+fn format_record(record: &Record, format: &Format, fields: &[String]) -> Result<String> {
+    Ok(match format {
+        Format::Plain => {
+            let values: Vec<_> = fields.iter().map(|f| render(record.get(f))).collect();
+            values.join("\t")
+        }
+        Format::Json | Format::JsonLines => serde_json::to_string(record)?,
+        Format::Csv => {
+            let values: Vec<_> = fields
+                .iter()
+                .map(|f| csv_escape(&render(record.get(f))))
+                .collect();
+            values.join(",")
+        }
+        Format::Template(t) => {
+            let mut result = String::new();
+            let mut rest = &**t;
+            while let Some(start) = rest.find('{') {
+                let end = start + rest[start..].find('}').unwrap();
+                result.push_str(&rest[..start]);
+                result.push_str(&render(record.get(&rest[start + 1..end])));
+                rest = &rest[end + 1..];
+            }
+            result.push_str(rest);
+            result
+        }
+    })
+}
+
+fn to_record(metadata: &RfcMetadata) -> Result<Record> {
+    // Not via `serde_json::Value`, which would sort the fields.
+    Ok(serde_json::from_str(&serde_json::to_string(metadata)?)?)
+}
+
+/// Select the named fields from an RFC's metadata, in the order given.
+fn select(metadata: &RfcMetadata, fields: &[String]) -> Result<Record> {
+    let mut all = to_record(metadata)?;
+    fields
+        .iter()
+        .map(|f| match all.remove(f) {
+            Some(v) => Ok((f.clone(), v)),
+            None => Err(Error::ParseArg(f.clone())),
+        })
+        .collect::<Result<_>>()
+        .map(Record)
+}
+
+/// Render a value as plain text.
+fn render(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(a) => a.iter().map(render).collect::<Vec<_>>().join(", "),
+        v => v.to_string(),
+    }
+}
+
+fn csv_escape(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+/// The names of the fields used in a template.
+fn template_fields(template: &str) -> Result<Vec<String>> {
+    let mut result = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => return Err(Error::ParseArg(template.to_owned())),
+        };
+        let field = rest[start + 1..end].to_owned();
+        if !result.contains(&field) {
+            result.push(field);
+        }
+        rest = &rest[end + 1..];
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_escape_() {
+        assert_eq!(csv_escape("Private fields"), "Private fields");
+        assert_eq!(csv_escape("A-traits, A-dyn"), "\"A-traits, A-dyn\"");
+        assert_eq!(
+            csv_escape("The \"?\" operator"),
+            "\"The \"\"?\"\" operator\""
+        );
+        assert_eq!(csv_escape("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn template_fields_() {
+        assert_eq!(
+            template_fields("{number}: {title} ({number})").unwrap(),
+            vec!["number", "title"]
+        );
+        assert!(template_fields("no fields").unwrap().is_empty());
+        assert!(template_fields("{number").is_err());
+        let template = Format::Template("{title}".to_owned());
+        assert!(check_fields(&template, &["number".to_owned()]).is_err());
+        assert!(check_fields(&template, &[]).is_ok());
+        assert!(check_fields(&Format::Csv, &["number".to_owned()]).is_ok());
+        assert!("{number".parse::<Format>().is_err());
+        assert_eq!(
+            "{title}".parse::<Format>().unwrap(),
+            Format::Template("{title}".to_owned())
+        );
+    }
+
+    #[test]
+    fn format_record_() {
+        let mut metadata = RfcMetadata::new(1, "0001-private-fields.md".to_owned(), None);
+        metadata.title = Some("Private fields, again".to_owned());
+        metadata.tags = vec!["A-privacy".to_owned(), "A-structs".to_owned()];
+        let fields: Vec<String> = ["title", "number", "tags", "start_date"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        let record = select(&metadata, &fields).unwrap();
+        let format = |format: Format| format_record(&record, &format, &fields).unwrap();
+
+        assert_eq!(
+            format(Format::Plain),
+            "Private fields, again\t1\tA-privacy, A-structs\t"
+        );
+        // Fields are kept in the order given.
+        assert_eq!(
+            format(Format::JsonLines),
+            r#"{"title":"Private fields, again","number":1,"tags":["A-privacy","A-structs"],"start_date":null}"#
+        );
+        assert_eq!(
+            format(Format::Csv),
+            "\"Private fields, again\",1,\"A-privacy, A-structs\","
+        );
+        assert_eq!(
+            format(Format::Template("RFC {number}: {title}.".to_owned())),
+            "RFC 1: Private fields, again."
+        );
+
+        assert!(select(&metadata, &["nonsense".to_owned()]).is_err());
+        let all = all_fields().unwrap();
+        assert_eq!(&all[..3], ["number", "filename", "start_date"]);
+    }
+}
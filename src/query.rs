@@ -0,0 +1,539 @@
+//! A small query language for selecting RFCs by their metadata.
+//!
+//! A query is a boolean expression over predicates, e.g.,
+//! `team:lang and tag:A-traits and not title:missing and start_date>=2018-01-01`.
+//!
+//! Predicates have the form `field op value`, where `op` is one of:
+//!
+//! * `:` matches: a case-insensitive substring for text fields, a prefix for `start_date` (e.g.,
+//!   `start_date:2018`), or any element of a list field,
+//! * `=` exact equality (for list fields, of any element),
+//! * `<`, `<=`, `>`, `>=` ordering (for `number` and `start_date`).
+//!
+//! The value `*` matches any non-empty field, so `not tag:*` selects RFCs without tags. Values
+//! containing spaces or parentheses can be quoted, e.g., `title:"private fields"`.
+//!
+//! `references` and `referenced_by` can be matched against an RFC number, or follow the graph of
+//! references with a nested query in parentheses, e.g., `references:(team:lang)` selects RFCs
+//! which refer to any lang RFC.
+//!
+//! Predicates can be combined with `and`, `or`, `not`, and parentheses. Adjacent predicates are
+//! combined with `and`.
+
+use crate::{
+    errors::{Error, Result},
+    metadata::{parse_date, IssueRef, RfcMetadata, Status, Team, TeamMetadata},
+};
+use std::{cmp::Ordering, collections::HashMap, str::FromStr};
+
+#[derive(Debug, Clone)]
+pub struct Query(Expr);
+
+impl Query {
+    pub fn matches(&self, metadata: &RfcMetadata, rfcs: &Rfcs<'_>) -> bool {
+        self.0.matches(metadata, rfcs)
+    }
+
+    /// The numbers of the RFCs in `all` which match the query.
+    pub fn matching(&self, all: &[RfcMetadata]) -> Vec<u64> {
+        let rfcs = Rfcs::new(all);
+        all.iter()
+            .filter(|m| self.matches(m, &rfcs))
+            .map(|m| m.number)
+            .collect()
+    }
+
+    /// Check that the teams in the query are in the team metadata. The team metadata is not
+    /// available when a query is parsed, so this must be checked before running the query.
+    pub fn check_teams(&self, team_metadata: &TeamMetadata) -> Result<()> {
+        self.0.check_teams(team_metadata)
+    }
+}
+
+/// All RFCs, by number, so that queries can follow references between RFCs.
+pub struct Rfcs<'a>(HashMap<u64, &'a RfcMetadata>);
+
+impl<'a> Rfcs<'a> {
+    pub fn new(all: &'a [RfcMetadata]) -> Rfcs<'a> {
+        Rfcs(all.iter().map(|m| (m.number, m)).collect())
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Query> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or_expr()?;
+        match parser.peek() {
+            None => Ok(Query(expr)),
+            Some(t) => Err(Error::ParseQuery(format!("unexpected `{}`", t))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Predicate(Predicate),
+    /// Any of the RFCs in the field (`references` or `referenced_by`) matches the expression.
+    Follow(Field, Box<Expr>),
+}
+
+impl Expr {
+    fn matches(&self, metadata: &RfcMetadata, rfcs: &Rfcs<'_>) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(metadata, rfcs) && b.matches(metadata, rfcs),
+            Expr::Or(a, b) => a.matches(metadata, rfcs) || b.matches(metadata, rfcs),
+            Expr::Not(e) => !e.matches(metadata, rfcs),
+            Expr::Predicate(p) => p.matches(metadata),
+            Expr::Follow(field, e) => field
+                .rfc_numbers(metadata)
+                .iter()
+                .filter_map(|n| rfcs.0.get(n))
+                .any(|m| e.matches(m, rfcs)),
+        }
+    }
+
+    fn check_teams(&self, team_metadata: &TeamMetadata) -> Result<()> {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.check_teams(team_metadata)?;
+                b.check_teams(team_metadata)
+            }
+            Expr::Not(e) | Expr::Follow(_, e) => e.check_teams(team_metadata),
+            Expr::Predicate(Predicate {
+                field: Field::Teams,
+                value: Some(value),
+                ..
+            }) => team_metadata.parse(value).map(|_| ()),
+            Expr::Predicate(_) => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Field {
+    Number,
+    Filename,
+    StartDate,
+    FeatureName,
+    Issues,
+    Title,
+    Teams,
+    Tags,
+    Status,
+    References,
+    ReferencedBy,
+}
+
+impl Field {
+    /// The RFCs referred to by a field which is a list of RFC numbers.
+    fn rfc_numbers(self, metadata: &RfcMetadata) -> &[u64] {
+        match self {
+            Field::References => &metadata.references,
+            Field::ReferencedBy => &metadata.referenced_by,
+            _ => &[],
+        }
+    }
+}
+
+impl FromStr for Field {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Field> {
+        match s {
+            "number" => Ok(Field::Number),
+            "filename" => Ok(Field::Filename),
+            "start_date" => Ok(Field::StartDate),
+            "feature_name" | "feature" => Ok(Field::FeatureName),
+            "issues" | "issue" => Ok(Field::Issues),
+            "title" => Ok(Field::Title),
+            "teams" | "team" => Ok(Field::Teams),
+            "tags" | "tag" => Ok(Field::Tags),
+            "status" => Ok(Field::Status),
+            "references" | "refs" => Ok(Field::References),
+            "referenced_by" => Ok(Field::ReferencedBy),
+            _ => Err(Error::ParseQuery(format!("unknown field `{}`", s))),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Op {
+    Matches,
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn compare(self, ord: Ordering) -> bool {
+        match self {
+            Op::Matches | Op::Eq => ord == Ordering::Equal,
+            Op::Lt => ord == Ordering::Less,
+            Op::Le => ord != Ordering::Greater,
+            Op::Gt => ord == Ordering::Greater,
+            Op::Ge => ord != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Predicate {
+    field: Field,
+    op: Op,
+    /// `None` for the wildcard (`*`).
+    value: Option<String>,
+}
+
+impl Predicate {
+    fn parse(s: &str) -> Result<Predicate> {
+        let start = s
+            .find([':', '=', '<', '>'])
+            .ok_or_else(|| Error::ParseQuery(format!("expected a predicate, found `{}`", s)))?;
+        let field: Field = s[..start].parse()?;
+        let rest = &s[start..];
+        let (op, value) = if let Some(v) = rest.strip_prefix("<=") {
+            (Op::Le, v)
+        } else if let Some(v) = rest.strip_prefix(">=") {
+            (Op::Ge, v)
+        } else if let Some(v) = rest.strip_prefix('<') {
+            (Op::Lt, v)
+        } else if let Some(v) = rest.strip_prefix('>') {
+            (Op::Gt, v)
+        } else if let Some(v) = rest.strip_prefix('=') {
+            (Op::Eq, v)
+        } else {
+            (Op::Matches, &rest[1..])
+        };
+
+        let value = unquote(value);
+        let value = if value == "*" {
+            None
+        } else {
+            Some(value.to_owned())
+        };
+
+        let ordered = matches!(op, Op::Lt | Op::Le | Op::Gt | Op::Ge);
+        if ordered && !matches!(field, Field::Number | Field::StartDate) {
+            return Err(Error::ParseQuery(format!(
+                "`{}` can't be compared with an ordering operator",
+                &s[..start]
+            )));
+        }
+        if let Some(value) = &value {
+            // Check values early so that a typo doesn't silently match nothing.
+            match field {
+                Field::Number | Field::References | Field::ReferencedBy => {
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| Error::ParseQuery(format!("not a number: `{}`", value)))?;
+                }
+                Field::Teams => {
+                    value.parse::<Team>()?;
+                }
+                Field::Status => {
+                    value.parse::<Status>()?;
+                }
+                Field::StartDate if ordered || op == Op::Eq => {
+                    parse_date(value)
+                        .ok_or_else(|| Error::ParseQuery(format!("not a date: `{}`", value)))?;
+                }
+                _ => {}
+            }
+        } else if ordered {
+            return Err(Error::ParseQuery(format!(
+                "can't compare with `*`: `{}`",
+                s
+            )));
+        }
+
+        // Compare issues and dates canonically, so that `issue=#1234` finds `rust-lang/rust#1234`
+        // and `start_date=2015-1-5` finds `2015-01-05`.
+        let value = match (field, op, value) {
+            (Field::Issues, Op::Eq, Some(v)) => Some(v.parse::<IssueRef>()?.to_string()),
+            (Field::StartDate, Op::Eq, Some(v)) => parse_date(&v).map(|d| d.to_string()),
+            (_, _, v) => v,
+        };
+
+        Ok(Predicate { field, op, value })
+    }
+
+    fn matches(&self, metadata: &RfcMetadata) -> bool {
+        let value = match &self.value {
+            Some(v) => v,
+            None => {
+                return match self.field {
+                    Field::Number | Field::Status => true,
+                    Field::Filename => !metadata.filename.is_empty(),
+                    Field::StartDate => metadata.start_date.is_some(),
+                    Field::FeatureName => !metadata.feature_name.is_empty(),
+                    Field::Issues => !metadata.issues.is_empty(),
+                    Field::Title => metadata.title.is_some(),
+                    Field::Teams => !metadata.teams.is_empty(),
+                    Field::Tags => !metadata.tags.is_empty(),
+                    Field::References => !metadata.references.is_empty(),
+                    Field::ReferencedBy => !metadata.referenced_by.is_empty(),
+                }
+            }
+        };
+
+        match self.field {
+            Field::Number => self
+                .op
+                .compare(metadata.number.cmp(&value.parse().unwrap())),
+            Field::StartDate => match (metadata.start_date, self.op) {
+                (None, _) => false,
+                (Some(date), Op::Matches) => date.to_string().starts_with(&**value),
+                (Some(date), Op::Eq) => date.to_string() == *value,
+                (Some(date), op) => op.compare(date.cmp(&parse_date(value).unwrap())),
+            },
+            Field::Filename => self.text_matches(&metadata.filename, value),
+            Field::Title => match &metadata.title {
+                Some(t) => self.text_matches(t, value),
+                None => false,
+            },
+            Field::FeatureName => metadata
+                .feature_name
+                .iter()
+                .any(|f| f.eq_ignore_ascii_case(value)),
+            Field::Issues => metadata
+                .issues
+                .iter()
+                .any(|i| self.text_matches(&i.to_string(), value)),
+            Field::Tags => metadata.tags.iter().any(|t| t.eq_ignore_ascii_case(value)),
+            Field::Teams => metadata.teams.contains(&value.parse().unwrap()),
+            Field::Status => metadata.status == value.parse().unwrap(),
+            Field::References | Field::ReferencedBy => self
+                .field
+                .rfc_numbers(metadata)
+                .contains(&value.parse().unwrap()),
+        }
+    }
+
+    fn text_matches(&self, text: &str, value: &str) -> bool {
+        match self.op {
+            Op::Eq => text == value,
+            _ => contains(text, value),
+        }
+    }
+}
+
+fn contains(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+fn unquote(s: &str) -> &str {
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        &s[1..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+/// Splits a query into words and parentheses. Quoted strings are kept within a single word.
+fn tokenize(input: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut buf = String::new();
+    let mut quoted = false;
+    for c in input.chars() {
+        if quoted {
+            buf.push(c);
+            if c == '"' {
+                quoted = false;
+            }
+        } else if c == '"' {
+            quoted = true;
+            buf.push(c);
+        } else if c.is_whitespace() || c == '(' || c == ')' {
+            if !buf.is_empty() {
+                tokens.push(buf);
+                buf = String::new();
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            buf.push(c);
+        }
+    }
+
+    if quoted {
+        return Err(Error::ParseQuery("unterminated quote".to_owned()));
+    }
+    if !buf.is_empty() {
+        tokens.push(buf);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(|t| &**t)
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(t) if t.eq_ignore_ascii_case(keyword) => {
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or_expr(&mut self) -> Result<Expr> {
+        let mut result = self.and_expr()?;
+        while self.eat_keyword("or") {
+            result = Expr::Or(Box::new(result), Box::new(self.and_expr()?));
+        }
+        Ok(result)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut result = self.not_expr()?;
+        loop {
+            if !self.eat_keyword("and") {
+                // Adjacent terms are implicitly and-ed.
+                match self.peek() {
+                    None | Some(")") => break,
+                    Some(t) if t.eq_ignore_ascii_case("or") => break,
+                    _ => {}
+                }
+            }
+            result = Expr::And(Box::new(result), Box::new(self.not_expr()?));
+        }
+        Ok(result)
+    }
+
+    fn not_expr(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") {
+            Ok(Expr::Not(Box::new(self.not_expr()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some("(") => {
+                self.next += 1;
+                let result = self.or_expr()?;
+                if self.peek() != Some(")") {
+                    return Err(Error::ParseQuery("expected `)`".to_owned()));
+                }
+                self.next += 1;
+                Ok(result)
+            }
+            Some(t) => {
+                if let Some(field) = t.strip_suffix(':') {
+                    let field: Field = field.parse()?;
+                    let followable = matches!(field, Field::References | Field::ReferencedBy);
+                    if followable && self.tokens.get(self.next + 1).map(|t| &**t) == Some("(") {
+                        self.next += 1;
+                        // Parses the parenthesised query.
+                        let e = self.primary()?;
+                        return Ok(Expr::Follow(field, Box::new(e)));
+                    }
+                }
+                let result = Predicate::parse(t)?;
+                self.next += 1;
+                Ok(Expr::Predicate(result))
+            }
+            None => Err(Error::ParseQuery("unexpected end of query".to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::metadata::TeamInfo;
+
+    #[test]
+    fn query_() {
+        let mut rfc = RfcMetadata::new(
+            1,
+            "0001-private-fields.md".to_owned(),
+            parse_date("2014-03-11"),
+        );
+        rfc.title = Some("Private fields".to_owned());
+        rfc.teams = vec!["lang".parse().unwrap()];
+        rfc.tags = vec!["A-privacy".to_owned(), "A-data-types".to_owned()];
+
+        let rfcs = Rfcs::new(&[]);
+        let matches = |q: &str| q.parse::<Query>().unwrap().matches(&rfc, &rfcs);
+        assert!(matches("team:lang"));
+        assert!(matches("team:lang tag:a-privacy"));
+        assert!(matches("team:lang and tag:A-privacy and not title:missing"));
+        assert!(matches("title:\"private fields\""));
+        assert!(!matches("title=private"));
+        assert!(matches("start_date>=2014-01-01 and start_date<2015-01-01"));
+        assert!(matches("start_date:2014-03 and not start_date=2014-03-12"));
+        assert!(matches("start_date=2014-3-11 and start_date=11/03/2014"));
+        assert!(matches("number<=1 and status:merged"));
+        assert!(matches(
+            "(team:libs or team:lang) and not (tag:A-traits or issue:*)"
+        ));
+        assert!(matches("tag:* and not feature:*"));
+        rfc.issues = vec!["rust-lang/rust#8122".parse().unwrap()];
+        let matches = |q: &str| q.parse::<Query>().unwrap().matches(&rfc, &rfcs);
+        assert!(matches("issue=#8122 and issue:8122"));
+        assert!(matches(
+            "issue=https://github.com/rust-lang/rust/issues/8122"
+        ));
+        assert!(!matches("issue=rust-lang/cargo#8122"));
+        assert!(!matches("not team:lang or team:libs"));
+
+        assert!("team:dev-tools".parse::<Query>().is_err());
+        assert!("colour:red".parse::<Query>().is_err());
+        assert!("title>foo".parse::<Query>().is_err());
+        assert!("start_date=2014-03".parse::<Query>().is_err());
+        assert!("(team:lang".parse::<Query>().is_err());
+        assert!("team:lang)".parse::<Query>().is_err());
+        assert!("team:lang and".parse::<Query>().is_err());
+        assert!("refs:foo".parse::<Query>().is_err());
+
+        let teams = TeamMetadata {
+            teams: vec![TeamInfo {
+                id: "lang".parse().unwrap(),
+                name: None,
+                description: None,
+                labels: Vec::new(),
+            }],
+        };
+        let check = |q: &str| q.parse::<Query>().unwrap().check_teams(&teams);
+        assert!(check("team:lang or refs:(team:Lang)").is_ok());
+        assert!(check("title:foo or not refs:(team:foo)").is_err());
+    }
+
+    #[test]
+    fn follow_references() {
+        let mut all: Vec<_> = (1..=3)
+            .map(|n| RfcMetadata::new(n, String::new(), None))
+            .collect();
+        all[0].teams = vec!["lang".parse().unwrap()];
+        all[1].references = vec![1];
+        all[0].referenced_by = vec![2];
+        all[2].references = vec![2];
+        all[1].referenced_by = vec![3];
+
+        let matching = |q: &str| q.parse::<Query>().unwrap().matching(&all);
+        assert_eq!(matching("references=1"), vec![2]);
+        assert_eq!(matching("refs:*"), vec![2, 3]);
+        assert_eq!(matching("references:(team:lang)"), vec![2]);
+        assert_eq!(matching("references:(references:(number=1))"), vec![3]);
+        assert_eq!(matching("referenced_by:(number>2) or number=3"), vec![2, 3]);
+    }
+}
@@ -0,0 +1,219 @@
+//! Keeping requests to GitHub's API within its rate limits.
+//!
+//! GitHub reports the state of the rate limit in the `X-RateLimit-*` headers of each response. Once
+//! the limit has been used up, requests wait until it resets. If GitHub rejects a request because
+//! of its primary or secondary rate limit, the request is retried with backoff.
+
+use crate::errors::{Error, Result};
+use octocrab::Octocrab;
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    RequestBuilder, Response, StatusCode,
+};
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The longest we'll wait for the rate limit to reset before giving up.
+const MAX_WAIT: Duration = Duration::from_secs(15 * 60);
+const MAX_RETRIES: u32 = 5;
+
+/// The most recently reported state of the rate limit.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Limit {
+    remaining: u64,
+    /// When the limit resets, in seconds since the Unix epoch.
+    reset: u64,
+}
+
+impl Limit {
+    /// The limit reported by a response, given the previously reported limit.
+    fn update(previous: Option<Limit>, headers: &HeaderMap) -> Option<Limit> {
+        let (remaining, reset) = match (
+            header_u64(headers, "x-ratelimit-remaining"),
+            header_u64(headers, "x-ratelimit-reset"),
+        ) {
+            (Some(remaining), Some(reset)) => (remaining, reset),
+            _ => return previous,
+        };
+
+        // Responses to concurrent requests can arrive out of order.
+        let remaining = match previous {
+            Some(l) if l.reset == reset => l.remaining.min(remaining),
+            _ => remaining,
+        };
+        Some(Limit { remaining, reset })
+    }
+
+    /// If the limit has been used up, how long until it resets.
+    fn wait(&self, now: u64) -> Option<Duration> {
+        if self.remaining == 0 && self.reset >= now {
+            // Allow a second for clock differences.
+            Some(Duration::from_secs(self.reset - now + 1))
+        } else {
+            None
+        }
+    }
+}
+
+static LIMIT: Mutex<Option<Limit>> = Mutex::new(None);
+
+/// Send a request, waiting for the rate limit if necessary. `request` must not have a streaming
+/// body, so that it can be retried. If the request is still rate limited after several retries, the
+/// error response is returned.
+pub async fn send(octocrab: &Octocrab, request: RequestBuilder) -> Result<Response> {
+    let mut attempt = 0;
+    loop {
+        if let Some(wait) = wait_for_reset() {
+            pause(wait).await?;
+        }
+
+        let response = octocrab
+            .execute(request.try_clone().expect("request can't be retried"))
+            .await?;
+        update(response.headers());
+        if !is_rate_limited(response.status(), response.headers()) || attempt == MAX_RETRIES {
+            return Ok(response);
+        }
+
+        let wait = backoff(response.headers(), *LIMIT.lock().unwrap(), now(), attempt);
+        attempt += 1;
+        pause(wait).await?;
+    }
+}
+
+async fn pause(wait: Duration) -> Result<()> {
+    if wait > MAX_WAIT {
+        return Err(Error::RateLimited(format!(
+            "the limit resets in {} minutes",
+            wait.as_secs() / 60 + 1
+        )));
+    }
+    eprintln!("Rate limited by GitHub, waiting {} seconds", wait.as_secs());
+    tokio::time::sleep(wait).await;
+    Ok(())
+}
+
+fn is_rate_limited(status: StatusCode, headers: &HeaderMap) -> bool {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::FORBIDDEN => {
+            header_u64(headers, "x-ratelimit-remaining") == Some(0)
+                || headers.contains_key(RETRY_AFTER)
+        }
+        _ => false,
+    }
+}
+
+/// How long to wait before retrying a rate limited request: as long as GitHub asks, or until the
+/// limit resets, or else exponentially longer for each attempt.
+fn backoff(headers: &HeaderMap, limit: Option<Limit>, now: u64, attempt: u32) -> Duration {
+    header_u64(headers, RETRY_AFTER.as_str())
+        .map(Duration::from_secs)
+        .or_else(|| limit?.wait(now))
+        .unwrap_or_else(|| Duration::from_secs(2u64.pow(attempt)))
+}
+
+/// If the rate limit has been used up, how long until it resets.
+fn wait_for_reset() -> Option<Duration> {
+    LIMIT.lock().unwrap().as_ref()?.wait(now())
+}
+
+fn update(headers: &HeaderMap) {
+    let mut limit = LIMIT.lock().unwrap();
+    *limit = Limit::update(*limit, headers);
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn is_rate_limited_() {
+        let empty = HeaderMap::new();
+        let used_up = headers(&[("x-ratelimit-remaining", "0")]);
+        let retry = headers(&[("retry-after", "60")]);
+        assert!(is_rate_limited(StatusCode::TOO_MANY_REQUESTS, &empty));
+        assert!(is_rate_limited(StatusCode::FORBIDDEN, &used_up));
+        assert!(is_rate_limited(StatusCode::FORBIDDEN, &retry));
+        // E.g., a private repo.
+        assert!(!is_rate_limited(StatusCode::FORBIDDEN, &empty));
+        assert!(!is_rate_limited(
+            StatusCode::FORBIDDEN,
+            &headers(&[("x-ratelimit-remaining", "10")])
+        ));
+        assert!(!is_rate_limited(StatusCode::OK, &used_up));
+    }
+
+    #[test]
+    fn limit_() {
+        let limit = |remaining, reset| Limit { remaining, reset };
+        let reported = |remaining: &str, reset: &str| {
+            headers(&[
+                ("x-ratelimit-remaining", remaining),
+                ("x-ratelimit-reset", reset),
+            ])
+        };
+
+        assert_eq!(Limit::update(None, &HeaderMap::new()), None);
+        assert_eq!(
+            Limit::update(Some(limit(5, 100)), &HeaderMap::new()),
+            Some(limit(5, 100))
+        );
+        assert_eq!(
+            Limit::update(None, &reported(" 10 ", "100")),
+            Some(limit(10, 100))
+        );
+        // A response which was sent earlier, but arrived later.
+        assert_eq!(
+            Limit::update(Some(limit(5, 100)), &reported("10", "100")),
+            Some(limit(5, 100))
+        );
+        // After a reset.
+        assert_eq!(
+            Limit::update(Some(limit(0, 100)), &reported("5000", "200")),
+            Some(limit(5000, 200))
+        );
+        assert_eq!(Limit::update(None, &reported("many", "100")), None);
+
+        assert_eq!(limit(0, 100).wait(90), Some(Duration::from_secs(11)));
+        assert_eq!(limit(0, 100).wait(101), None);
+        assert_eq!(limit(1, 100).wait(90), None);
+    }
+
+    #[test]
+    fn backoff_() {
+        let empty = HeaderMap::new();
+        let used_up = Some(Limit {
+            remaining: 0,
+            reset: 100,
+        });
+        assert_eq!(
+            backoff(&headers(&[("retry-after", "30")]), used_up, 90, 0),
+            Duration::from_secs(30)
+        );
+        assert_eq!(backoff(&empty, used_up, 90, 0), Duration::from_secs(11));
+        assert_eq!(backoff(&empty, None, 90, 0), Duration::from_secs(1));
+        assert_eq!(backoff(&empty, used_up, 200, 3), Duration::from_secs(8));
+    }
+}
@@ -0,0 +1,156 @@
+//! Finding references to other RFCs in the text of an RFC.
+//!
+//! References are written in many ways, e.g., `RFC 1234`, `RFC #1234`, `rust-lang/rfcs#1234`, a
+//! link to a PR (`rust-lang/rfcs/pull/1234`), or a link to an RFC's text (`text/1234-foo.md`, or
+//! `rust-lang.github.io/rfcs/1234-foo.html`).
+
+use crate::metadata::{leading_number, RfcMetadata};
+use std::collections::{BTreeSet, HashMap};
+
+/// Text which precedes the number of an RFC, and whether it must be followed by a file name
+/// (`1234-foo.md`) rather than just a number.
+const PREFIXES: &[(&str, bool)] = &[
+    ("rfc", false),
+    ("rfc ", false),
+    ("rfc #", false),
+    ("rfc-", false),
+    ("rfcs#", false),
+    ("rfcs/pull/", false),
+    ("text/", true),
+    ("rfcs/", true),
+];
+
+/// The numbers of the RFCs referred to in `text`, in ascending order.
+pub fn find_references(text: &str) -> Vec<u64> {
+    let text = text.to_lowercase();
+    let mut result = BTreeSet::new();
+    for (prefix, filename) in PREFIXES {
+        for (i, _) in text.match_indices(prefix) {
+            // `RFC 1234` must be a word, so that e.g. `xrfc 1` doesn't count.
+            let word_start = !text[..i]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric());
+            if prefix.starts_with("rfc") && !word_start {
+                continue;
+            }
+
+            let rest = &text[i + prefix.len()..];
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let valid = if *filename {
+                digits == 4 && rest[digits..].starts_with('-')
+            } else {
+                digits > 0 && digits <= 4 && !is_ietf(&text, i, i + prefix.len() + digits)
+            };
+            if !valid {
+                continue;
+            }
+            if let Some(n) = leading_number(rest).filter(|n| *n > 0) {
+                result.insert(n);
+            }
+        }
+    }
+    result.into_iter().collect()
+}
+
+/// Whether the reference at `text[start..end]` is to an IETF RFC (e.g., RFC 2119 for "MUST"):
+/// it is part of an IETF URL, is the text of a Markdown link to one, or follows "IETF".
+fn is_ietf(text: &str, start: usize, end: usize) -> bool {
+    let ietf = |s: &str| s.contains("ietf") || s.contains("rfc-editor");
+    let delimiter = |c: char| c.is_whitespace() || "()<>[]".contains(c);
+    // The rest of the word (e.g., a URL) which contains the reference.
+    let before = text[..start].split(delimiter).next_back().unwrap_or("");
+    let after = text[end..].split(delimiter).next().unwrap_or("");
+    // E.g., `[RFC 2119](https://www.ietf.org/rfc/rfc2119.txt)`.
+    let link = text[end..]
+        .strip_prefix("](")
+        .and_then(|s| s.split(')').next())
+        .unwrap_or("");
+    ietf(before) || ietf(after) || ietf(link) || text[..start].trim_end().ends_with("ietf")
+}
+
+/// Set `references` (to known RFCs, excluding itself) from the given text of each RFC, and
+/// `referenced_by` for every RFC. Returns the RFCs whose references changed.
+pub fn update_references<'a>(
+    metadata: &'a mut [RfcMetadata],
+    texts: &HashMap<u64, String>,
+) -> Vec<&'a RfcMetadata> {
+    let known: BTreeSet<u64> = metadata.iter().map(|m| m.number).collect();
+    let mut references = HashMap::new();
+    let mut referenced_by: HashMap<u64, Vec<u64>> = HashMap::new();
+    for m in metadata.iter() {
+        let refs: Vec<u64> = match texts.get(&m.number) {
+            Some(text) => find_references(text)
+                .into_iter()
+                .filter(|n| *n != m.number && known.contains(n))
+                .collect(),
+            // We don't have the text of proposals which weren't merged.
+            None => m.references.clone(),
+        };
+        for n in &refs {
+            referenced_by.entry(*n).or_default().push(m.number);
+        }
+        references.insert(m.number, refs);
+    }
+
+    let mut changed = Vec::new();
+    for m in metadata.iter_mut() {
+        let refs = references.remove(&m.number).unwrap_or_default();
+        let mut by = referenced_by.remove(&m.number).unwrap_or_default();
+        by.sort_unstable();
+        if m.references != refs || m.referenced_by != by {
+            m.references = refs;
+            m.referenced_by = by;
+            changed.push(&*m);
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_references_() {
+        let text = "This builds on RFC 1234 and [RFC #0016](https://github.com/rust-lang/rfcs/pull/16), \
+            see rust-lang/rfcs#2000 and [the text](https://rust-lang.github.io/rfcs/0401-coercions.html).\n\
+            Also text/1023-rebalancing-coherence.md, RFC-3 and rfc40.\n\
+            Keywords are as in [RFC 2119](https://www.ietf.org/rfc/rfc2119.txt), like RFC 1105.\n\
+            Not IETF RFC 7230, <https://datatracker.ietf.org/doc/html/rfc8259>, or \
+            https://www.rfc-editor.org/rfc/rfc9110.\n\
+            Not RFC 12345, rfcs/0000-template.md, or xrfc 5.";
+        assert_eq!(
+            find_references(text),
+            vec![3, 16, 40, 401, 1023, 1105, 1234, 2000]
+        );
+    }
+
+    #[test]
+    fn update_references_() {
+        let mut metadata: Vec<_> = (1..=3)
+            .map(|n| RfcMetadata::new(n, String::new(), None))
+            .collect();
+        // We have no text for RFC 3 (e.g., it was closed), so its references are kept.
+        metadata[2].references = vec![1];
+        let mut texts = HashMap::new();
+        texts.insert(1, "See RFC 2, RFC 1, and RFC 9.".to_owned());
+        texts.insert(2, "Amends RFC 1.".to_owned());
+
+        let changed: Vec<u64> = update_references(&mut metadata, &texts)
+            .iter()
+            .map(|m| m.number)
+            .collect();
+        assert_eq!(changed, vec![1, 2]);
+        assert_eq!(metadata[0].references, vec![2]);
+        assert_eq!(metadata[0].referenced_by, vec![2, 3]);
+        assert_eq!(metadata[1].references, vec![1]);
+        assert_eq!(metadata[1].referenced_by, vec![1]);
+        assert_eq!(metadata[2].references, vec![1]);
+        assert!(metadata[2].referenced_by.is_empty());
+
+        assert!(update_references(&mut metadata, &texts).is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+//! A static full-text search index for the website.
+//!
+//! The index is emitted as a script (so that it works when browsing the site locally) which is
+//! loaded and queried by the search box in `index.handlebars`. Terms map to the RFCs containing them
+//! with a tf-idf score; matches in the title and feature names are weighted more heavily than
+//! matches in the text. The plain text of each RFC is included for showing snippets.
+
+use crate::errors::Result;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::Write,
+    path::Path,
+};
+
+const TITLE_WEIGHT: f64 = 10.0;
+const FEATURE_WEIGHT: f64 = 5.0;
+
+const STOP_WORDS: &[&str] = &[
+    "an", "and", "are", "as", "at", "be", "but", "by", "can", "do", "for", "from", "has", "have",
+    "if", "in", "is", "it", "its", "of", "on", "or", "so", "that", "the", "then", "there", "these",
+    "this", "to", "was", "we", "which", "will", "with", "would",
+];
+
+#[derive(Default)]
+pub struct SearchIndex {
+    docs: Vec<Doc>,
+    /// term -> doc index -> weighted term frequency.
+    frequencies: HashMap<String, HashMap<usize, f64>>,
+}
+
+#[derive(Serialize)]
+struct Doc {
+    number: String,
+    title: String,
+    url: String,
+    text: String,
+}
+
+#[derive(Serialize)]
+struct SerializedIndex<'a> {
+    stop_words: &'a [&'a str],
+    docs: &'a [Doc],
+    /// term -> [(doc index, score * 100)], sorted by score.
+    terms: BTreeMap<&'a str, Vec<(usize, u32)>>,
+}
+
+impl SearchIndex {
+    pub fn new() -> SearchIndex {
+        SearchIndex::default()
+    }
+
+    /// Add an RFC to the index. `html` is the rendered text of the RFC.
+    pub fn add(
+        &mut self,
+        number: &str,
+        title: &str,
+        url: &str,
+        feature_names: &[String],
+        html: &str,
+    ) {
+        let text = plain_text(html);
+        let doc = self.docs.len();
+
+        let mut counts: HashMap<String, f64> = HashMap::new();
+        for t in tokenize(title) {
+            *counts.entry(t).or_default() += TITLE_WEIGHT;
+        }
+        for t in feature_names.iter().flat_map(|f| tokenize(f)) {
+            *counts.entry(t).or_default() += FEATURE_WEIGHT;
+        }
+        let mut body_counts: HashMap<String, f64> = HashMap::new();
+        for t in tokenize(&text) {
+            *body_counts.entry(t).or_default() += 1.0;
+        }
+        // Damp term frequency in the body so that long RFCs don't dominate.
+        for (t, c) in body_counts {
+            *counts.entry(t).or_default() += 1.0 + c.ln();
+        }
+
+        for (t, c) in counts {
+            self.frequencies.entry(t).or_default().insert(doc, c);
+        }
+        self.docs.push(Doc {
+            number: number.to_owned(),
+            title: title.to_owned(),
+            url: url.to_owned(),
+            text,
+        });
+    }
+
+    /// Write the index as a script which assigns the index to `searchIndex`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let n = self.docs.len() as f64;
+        let terms = self
+            .frequencies
+            .iter()
+            .map(|(t, docs)| {
+                let idf = (n / docs.len() as f64).ln() + 1.0;
+                let mut scores: Vec<_> = docs
+                    .iter()
+                    .map(|(d, tf)| (*d, (tf * idf * 100.0).round() as u32))
+                    .collect();
+                scores.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+                (&**t, scores)
+            })
+            .collect();
+        let index = SerializedIndex {
+            stop_words: STOP_WORDS,
+            docs: &self.docs,
+            terms,
+        };
+
+        let mut file = File::create(path)?;
+        write!(file, "window.searchIndex = ")?;
+        serde_json::to_writer(&mut file, &index)?;
+        writeln!(file, ";")?;
+        Ok(())
+    }
+}
+
+/// Whether `c` is part of a search term: `[\p{Alphabetic}\p{N}_]` in JavaScript.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Split text into lowercase search terms. Must match `tokenize` in `index.handlebars`.
+fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !is_word_char(c))
+        // Lengths in JavaScript are in UTF-16 code units.
+        .filter(|w| w.encode_utf16().count() > 1)
+        .filter(|w| !STOP_WORDS.contains(w))
+        .map(|w| w.to_owned())
+        .collect()
+}
+
+/// Strip tags from HTML and collapse whitespace.
+fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn plain_text_() {
+        assert_eq!(
+            plain_text("<h1>Summary</h1>\n<p>Use <code>impl&lt;T&gt;</code> &amp; more.</p>"),
+            "Summary Use impl<T> & more."
+        );
+        assert_eq!(
+            tokenize("The impl_trait feature, and RFC 1234!"),
+            vec!["impl_trait", "feature", "rfc", "1234"]
+        );
+        // Single characters are skipped, as in JavaScript (where `𝒳` has length 2).
+        assert_eq!(tokenize("É 日本 是 𝒳"), vec!["日本", "𝒳"]);
+        // `_` and combining marks stay inside a term, as in JavaScript.
+        assert_eq!(
+            tokenize("_feature_name हिन्दी"),
+            vec!["_feature_name", "हिन", "दी"]
+        );
+    }
+}
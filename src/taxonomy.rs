@@ -0,0 +1,227 @@
+//! Editing the tag taxonomy (see `Tag`), and the tags of RFCs which use the edited tags.
+//!
+//! Edits are made in memory. Operations which change RFCs return the numbers of the changed RFCs,
+//! so that the caller can save them together with the tag metadata.
+
+use crate::{
+    errors::{Error, Result},
+    metadata::{RfcMetadata, Tag, Team},
+};
+
+fn position(tags: &[Tag], tag: &str) -> Result<usize> {
+    tags.iter()
+        .position(|t| t.tag == tag)
+        .ok_or_else(|| Error::UnknownTag(tag.to_owned()))
+}
+
+/// Check that `name` is not already used as a tag or alias (ignoring case).
+fn check_unused<'a>(tags: impl IntoIterator<Item = &'a Tag>, name: &str) -> Result<()> {
+    let used = tags.into_iter().any(|t| {
+        t.tag.eq_ignore_ascii_case(name) || t.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+    });
+    if used {
+        return Err(Error::TagExists(name.to_owned()));
+    }
+    Ok(())
+}
+
+/// Replace `from` with `to` in the tags of each RFC (removing `from` if `to` is `None`). Returns
+/// the numbers of the changed RFCs.
+fn replace_in_rfcs(rfcs: &mut [RfcMetadata], from: &str, to: Option<&str>) -> Vec<u64> {
+    let mut changed = Vec::new();
+    for m in rfcs {
+        if !m.tags.iter().any(|t| t == from) {
+            continue;
+        }
+        let mut tags: Vec<String> = Vec::with_capacity(m.tags.len());
+        for t in &m.tags {
+            let t = if t == from { to } else { Some(&**t) };
+            if let Some(t) = t {
+                if !tags.iter().any(|u| u == t) {
+                    tags.push(t.to_owned());
+                }
+            }
+        }
+        m.tags = tags;
+        changed.push(m.number);
+    }
+    changed
+}
+
+/// Replace references to `from` by other tags (as their parent or replacement) with `to`.
+fn replace_in_tags(tags: &mut [Tag], from: &str, to: Option<&str>) {
+    for t in tags {
+        if t.parent.as_deref() == Some(from) {
+            t.parent = to.map(|s| s.to_owned());
+        }
+        if t.deprecated.as_deref() == Some(from) {
+            t.deprecated = to.map(|s| s.to_owned());
+        }
+    }
+}
+
+/// Add a new tag to the taxonomy.
+pub fn add(tags: &mut Vec<Tag>, tag: Tag) -> Result<()> {
+    check_unused(&*tags, &tag.tag)?;
+    for a in &tag.aliases {
+        check_unused(&*tags, a)?;
+    }
+    if let Some(parent) = &tag.parent {
+        position(tags, parent)?;
+    }
+    tags.push(tag);
+    Ok(())
+}
+
+/// Remove a tag from the taxonomy. Fails if any RFC has the tag, unless `force`, in which case the
+/// tag is removed from those RFCs. The tag's children take its parent.
+pub fn remove(
+    tags: &mut Vec<Tag>,
+    rfcs: &mut [RfcMetadata],
+    tag: &str,
+    force: bool,
+) -> Result<Vec<u64>> {
+    let i = position(tags, tag)?;
+    let used = rfcs
+        .iter()
+        .filter(|m| m.tags.iter().any(|t| t == tag))
+        .count();
+    if used > 0 && !force {
+        return Err(Error::TagInUse(tag.to_owned(), used));
+    }
+
+    let removed = tags.remove(i);
+    for t in tags.iter_mut() {
+        if t.parent.as_deref() == Some(tag) {
+            t.parent = removed.parent.clone();
+        }
+    }
+    replace_in_tags(tags, tag, None);
+    Ok(replace_in_rfcs(rfcs, tag, None))
+}
+
+/// Rename a tag, in the taxonomy and in every RFC. The old name is kept as an alias, so that PR
+/// labels and `tag --add` still resolve to the tag.
+pub fn rename(
+    tags: &mut [Tag],
+    rfcs: &mut [RfcMetadata],
+    from: &str,
+    to: &str,
+) -> Result<Vec<u64>> {
+    let i = position(tags, from)?;
+    // The tag's own aliases and a change of case are allowed.
+    check_unused(tags[..i].iter().chain(&tags[i + 1..]), to)?;
+
+    let tag = &mut tags[i];
+    tag.tag = to.to_owned();
+    tag.aliases.retain(|a| !a.eq_ignore_ascii_case(to));
+    if !from.eq_ignore_ascii_case(to) {
+        tag.aliases.push(from.to_owned());
+    }
+    replace_in_tags(tags, from, Some(to));
+    Ok(replace_in_rfcs(rfcs, from, Some(to)))
+}
+
+/// Set the teams of a tag.
+pub fn move_to_team(tags: &mut [Tag], tag: &str, teams: Vec<Team>) -> Result<()> {
+    let i = position(tags, tag)?;
+    tags[i].teams = teams;
+    Ok(())
+}
+
+/// Merge `from` into `into`: RFCs with `from` get `into` instead, and `from` and its aliases become
+/// aliases of `into`. `into` is added to the teams of `from`, and takes its children.
+pub fn merge(
+    tags: &mut Vec<Tag>,
+    rfcs: &mut [RfcMetadata],
+    from: &str,
+    into: &str,
+) -> Result<Vec<u64>> {
+    position(tags, into)?;
+    if from == into {
+        return Err(Error::ParseArg(format!(
+            "can't merge `{}` into itself",
+            from
+        )));
+    }
+    let merged = tags.remove(position(tags, from)?);
+
+    let i = position(tags, into)?;
+    let target = &mut tags[i];
+    for team in merged.teams {
+        if !target.teams.contains(&team) {
+            target.teams.push(team);
+        }
+    }
+    target.aliases.push(merged.tag);
+    target.aliases.extend(merged.aliases);
+    if target.description.is_none() {
+        target.description = merged.description;
+    }
+
+    replace_in_tags(tags, from, Some(into));
+    // `into` may have been a child of `from`.
+    let target = &mut tags[i];
+    if target.parent.as_deref() == Some(into) {
+        target.parent = merged.parent;
+    }
+    if target.deprecated.as_deref() == Some(into) {
+        target.deprecated = None;
+    }
+    Ok(replace_in_rfcs(rfcs, from, Some(into)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn edit_taxonomy() {
+        let lang: Team = "lang".parse().unwrap();
+        let tag = |t: &str, parent: Option<&str>| {
+            let mut tag = Tag::new(t.to_owned(), vec![lang.clone()]);
+            tag.parent = parent.map(|p| p.to_owned());
+            tag
+        };
+        let mut tags = vec![
+            tag("A-traits", None),
+            tag("A-dyn", Some("A-traits")),
+            tag("A-trait-object", Some("A-traits")),
+        ];
+        let mut rfcs: Vec<_> = (1..=3)
+            .map(|n| RfcMetadata::new(n, String::new(), None))
+            .collect();
+        rfcs[0].tags = vec!["A-dyn".to_owned(), "A-trait-object".to_owned()];
+        rfcs[1].tags = vec!["A-traits".to_owned()];
+
+        assert!(add(&mut tags, tag("a-DYN", None)).is_err());
+        assert!(add(&mut tags, tag("A-generics", Some("A-typesystem"))).is_err());
+        add(&mut tags, tag("A-typesystem", None)).unwrap();
+
+        assert!(rename(&mut tags, &mut rfcs, "A-traits", "A-dyn").is_err());
+        assert_eq!(
+            rename(&mut tags, &mut rfcs, "A-traits", "A-trait").unwrap(),
+            vec![2]
+        );
+        assert_eq!(rfcs[1].tags, vec!["A-trait"]);
+        assert_eq!(tags[0].aliases, vec!["A-traits"]);
+        assert_eq!(tags[1].parent.as_deref(), Some("A-trait"));
+
+        assert_eq!(
+            merge(&mut tags, &mut rfcs, "A-dyn", "A-trait-object").unwrap(),
+            vec![1]
+        );
+        assert_eq!(rfcs[0].tags, vec!["A-trait-object"]);
+        assert_eq!(tags[1].tag, "A-trait-object");
+        assert_eq!(tags[1].aliases, vec!["A-dyn"]);
+
+        assert!(remove(&mut tags, &mut rfcs, "A-trait", false).is_err());
+        assert_eq!(
+            remove(&mut tags, &mut rfcs, "A-trait", true).unwrap(),
+            vec![2]
+        );
+        assert!(rfcs[1].tags.is_empty());
+        assert_eq!(tags[0].tag, "A-trait-object");
+        assert_eq!(tags[0].parent, None);
+    }
+}
@@ -0,0 +1,162 @@
+//! Consistency checks over all metadata.
+
+use crate::{
+    errors::Result,
+    github::{get_merged_rfc_metadata, RfcRepo},
+    metadata::{
+        all_metadata_paths, read_metadata, read_tag_metadata, read_team_metadata, RfcMetadata,
+    },
+};
+use std::{collections::HashSet, fmt, path::Path};
+
+/// A problem found with the metadata.
+pub struct Problem {
+    pub number: u64,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:0>4}: {}", self.number, self.message)
+    }
+}
+
+/// Check all metadata against itself, the tag and team metadata, and the RFC repo.
+pub fn validate(repo: &RfcRepo) -> Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    macro_rules! problem {
+        ($number: expr, $($arg: tt)*) => {
+            problems.push(Problem {
+                number: $number,
+                message: format!($($arg)*),
+            })
+        };
+    }
+
+    let tag_metadata = read_tag_metadata()?;
+    let team_metadata = read_team_metadata()?;
+    let mut merged = Vec::new();
+    for m in get_merged_rfc_metadata(repo)? {
+        match m.number() {
+            Ok(n) => merged.push((n, m.filename().to_owned())),
+            Err(_) => eprintln!("warning: unexpected file in RFC repo: {}", m.filename()),
+        }
+    }
+    let filenames: HashSet<&str> = merged.iter().map(|(_, f)| &**f).collect();
+
+    let mut metadata = Vec::new();
+    for path in all_metadata_paths()? {
+        let number = file_number(&path);
+        match read_metadata(&path) {
+            Ok(m) => {
+                if m.number != number {
+                    problem!(number, "metadata is for RFC {}", m.number);
+                }
+                metadata.push(m);
+            }
+            Err(e) => problem!(number, "could not read metadata: {}", e),
+        }
+    }
+    metadata.sort();
+    let numbers: HashSet<u64> = metadata.iter().map(|m| m.number).collect();
+
+    for (n, filename) in &merged {
+        if !numbers.contains(n) {
+            problem!(*n, "merged RFC `{}` has no metadata", filename);
+        }
+    }
+
+    for m in &metadata {
+        let n = m.number;
+        if m.status.is_accepted() && !filenames.contains(&*m.filename) {
+            problem!(n, "`{}` does not exist in the RFC repo", m.filename);
+        }
+        if !filename_matches(m) {
+            problem!(n, "filename `{}` does not match number", m.filename);
+        }
+        if m.start_date.is_none() {
+            problem!(n, "no start date");
+        }
+        for t in &m.tags {
+            match tag_metadata.get(t) {
+                None => problem!(n, "tag `{}` is not in the tag metadata", t),
+                Some(tag) => {
+                    if let Some(d) = &tag.deprecated {
+                        problem!(n, "tag `{}` is deprecated, use `{}`", t, d);
+                    }
+                }
+            }
+        }
+        for t in duplicates(&m.tags) {
+            problem!(n, "duplicate tag `{}`", t);
+        }
+        for t in &m.teams {
+            if team_metadata.get(t).is_none() {
+                problem!(n, "team `{}` is not in the team metadata", t);
+            }
+        }
+        for t in duplicates(&m.teams) {
+            problem!(n, "duplicate team `{}`", t);
+        }
+    }
+
+    problems.sort_by_key(|p| p.number);
+    Ok(problems)
+}
+
+/// The RFC number from a metadata file's name, e.g., `metadata/0001.json`.
+fn file_number(path: &Path) -> u64 {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .and_then(|s| s.parse().ok())
+        .expect("metadata path without a number")
+}
+
+/// Proposals which were not accepted (open, closed, or postponed) keep the placeholder number in
+/// their filename (usually `0000`).
+fn filename_matches(metadata: &RfcMetadata) -> bool {
+    let prefix = format!("{:0>4}-", metadata.number);
+    metadata.filename.starts_with(&prefix) || !metadata.status.is_accepted()
+}
+
+fn duplicates<T: PartialEq + fmt::Display>(items: &[T]) -> Vec<&T> {
+    let mut result = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        if items[..i].contains(item) && !result.contains(&item) {
+            result.push(item);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::metadata::Status;
+
+    #[test]
+    fn filename_matches_() {
+        let rfc = |filename: &str, status| {
+            let mut m = RfcMetadata::new(1234, filename.to_owned(), None);
+            m.status = status;
+            m
+        };
+        assert!(filename_matches(&rfc("1234-foo.md", Status::Merged)));
+        assert!(!filename_matches(&rfc("0000-foo.md", Status::Merged)));
+        assert!(!filename_matches(&rfc("1235-foo.md", Status::Stabilized)));
+        assert!(filename_matches(&rfc("0000-foo.md", Status::Proposed)));
+        assert!(filename_matches(&rfc("0000-foo.md", Status::Fcp)));
+        assert!(filename_matches(&rfc("0000-foo.md", Status::Closed)));
+        assert!(filename_matches(&rfc("0000-foo.md", Status::Postponed)));
+    }
+
+    #[test]
+    fn duplicates_() {
+        assert!(duplicates::<String>(&[]).is_empty());
+        assert!(duplicates(&["A-traits", "A-dyn"]).is_empty());
+        assert_eq!(
+            duplicates(&["A-traits", "A-dyn", "A-traits", "A-dyn", "A-traits"]),
+            vec![&"A-traits", &"A-dyn"]
+        );
+    }
+}